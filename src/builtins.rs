@@ -0,0 +1,109 @@
+use chrono::{DateTime, Datelike, FixedOffset, Local, NaiveDate, Utc};
+use indexmap::IndexMap;
+use serde::Serialize;
+use std::{env, path::Path, process::Command, sync::OnceLock};
+use tera::Context;
+use tracing::warn;
+
+/// The name under which the built-in values are available in every template.
+pub const NAMESPACE: &str = "templatex";
+
+/// The date given with `--date`, which takes precedence over `SOURCE_DATE_EPOCH`.
+static DATE_OVERRIDE: OnceLock<DateTime<FixedOffset>> = OnceLock::new();
+
+/// Values that templatex provides to every template without prompting, as `templatex.<field>`.
+#[derive(Serialize, Debug, Clone)]
+pub struct Builtins {
+    /// The name of the project, i.e. the name of the output directory.
+    pub name: String,
+    pub out_dir: String,
+    pub template: String,
+    pub source_dir: String,
+    /// The current date as `YYYY-MM-DD`.
+    pub date: String,
+    pub year: i32,
+    /// `user.name` from the git config, or an empty string.
+    pub user_name: String,
+    /// `user.email` from the git config, or an empty string.
+    pub user_email: String,
+    pub version: String,
+    /// The paths generated from each `foreach` file, relative to the project's `src`
+    /// directory, keyed by the file's path in the template. Filled in while rendering.
+    pub files: IndexMap<String, Vec<String>>,
+}
+
+impl Builtins {
+    pub fn new(out_dir: &Path, template: &str, source_dir: &Path) -> Self {
+        let date = build_date();
+        Self {
+            name: project_name(out_dir),
+            out_dir: out_dir.display().to_string(),
+            template: template.to_string(),
+            source_dir: source_dir.display().to_string(),
+            date: date.format("%Y-%m-%d").to_string(),
+            year: date.year(),
+            user_name: git_config("user.name"),
+            user_email: git_config("user.email"),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            files: IndexMap::new(),
+        }
+    }
+
+    pub fn insert_into(&self, context: &mut Context) {
+        context.insert(NAMESPACE, self);
+    }
+}
+
+/// The name of the project in `out_dir`. Resolves `.` to the name of the current directory.
+pub fn project_name(out_dir: &Path) -> String {
+    std::path::absolute(out_dir)
+        .ok()
+        .and_then(|p| p.file_name().map(|n| n.display().to_string()))
+        .unwrap_or_default()
+}
+
+/// The date a project is generated on. Uses the date set with [`set_date`], or else
+/// `SOURCE_DATE_EPOCH` (in UTC) if it is set, so that generated output can be reproduced.
+pub fn build_date() -> DateTime<FixedOffset> {
+    if let Some(date) = DATE_OVERRIDE.get() {
+        return *date;
+    }
+    if let Ok(epoch) = env::var("SOURCE_DATE_EPOCH") {
+        let date = epoch
+            .trim()
+            .parse::<i64>()
+            .ok()
+            .and_then(|secs| DateTime::<Utc>::from_timestamp(secs, 0));
+        match date {
+            Some(date) => return date.fixed_offset(),
+            None => warn!("Ignoring invalid SOURCE_DATE_EPOCH: {}", epoch),
+        }
+    }
+    Local::now().fixed_offset()
+}
+
+/// Pins the date returned by [`build_date`] for the rest of the run.
+pub fn set_date(date: DateTime<FixedOffset>) {
+    if DATE_OVERRIDE.set(date).is_err() {
+        warn!("The build date is already set");
+    }
+}
+
+/// Parses a date given as `YYYY-MM-DD` (midnight in UTC) or as an RFC 3339 timestamp.
+pub fn parse_date(s: &str) -> Result<DateTime<FixedOffset>, String> {
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Ok(date.and_time(Default::default()).and_utc().fixed_offset());
+    }
+    DateTime::parse_from_rfc3339(s)
+        .map_err(|_| format!("expected YYYY-MM-DD or an RFC 3339 timestamp, got `{s}`"))
+}
+
+fn git_config(key: &str) -> String {
+    Command::new("git")
+        .args(["config", "--get", key])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_default()
+}
@@ -2,7 +2,11 @@ use std::path::PathBuf;
 
 use clap::Parser;
 
-use crate::{config::get_config_dir, logging::get_data_dir};
+use chrono::{DateTime, FixedOffset};
+
+use crate::{
+    builtins::parse_date, config::get_config_dir, conflicts::ConflictPolicy, logging::get_data_dir,
+};
 
 /// A template engine for LaTeX projects
 ///
@@ -15,8 +19,8 @@ use crate::{config::get_config_dir, logging::get_data_dir};
 #[command(author, version = version(), about)]
 pub struct Cli {
     /// The name of the project. Use `.` for the current directory.
-    #[clap(required = true)]
-    pub name: String,
+    #[clap(required_unless_present_any = ["history", "clear_history"])]
+    pub name: Option<String>,
     #[clap(flatten)]
     pub args: Args,
 }
@@ -48,6 +52,92 @@ pub struct Args {
     /// %APPDATA%/templatex/config on Windows
     #[clap(long)]
     pub config_dir: Option<PathBuf>,
+    /// The name of the template to use. Skips the interactive picker.
+    #[clap(long)]
+    pub template: Option<String>,
+    /// Set the value of a template variable. Can be given multiple times.
+    #[clap(long = "set", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    pub set: Vec<(String, String)>,
+    /// A TOML or JSON file with values for the template variables.
+    /// Values passed with `--set` take precedence.
+    #[clap(long, value_name = "FILE")]
+    pub answers: Option<PathBuf>,
+    /// Never prompt. Variables without a value or default are reported
+    /// as an error instead. Remembered answers are not used.
+    #[clap(long)]
+    pub no_input: bool,
+    /// Don't prefill the answers given the last time the template was used.
+    #[clap(long)]
+    pub fresh: bool,
+    /// Show the remembered answers for a template and exit.
+    #[clap(long, value_name = "TEMPLATE", conflicts_with = "clear_history")]
+    pub history: Option<String>,
+    /// Forget the remembered answers for a template and exit.
+    #[clap(long, value_name = "TEMPLATE")]
+    pub clear_history: Option<String>,
+    /// Print what would be generated, as a tree or as JSON, without
+    /// writing anything.
+    #[clap(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        default_missing_value = "tree"
+    )]
+    pub dry_run: Option<PlanFormat>,
+    /// Overwrite files that already exist in the output directory.
+    #[clap(long, group = "conflicts")]
+    pub force: bool,
+    /// Keep files that already exist in the output directory.
+    #[clap(long, group = "conflicts")]
+    pub skip_existing: bool,
+    /// Show a diff for each file that already exists in the output
+    /// directory and ask whether to keep, overwrite or merge it.
+    #[clap(short, long, group = "conflicts")]
+    pub interactive: bool,
+    /// Generate the project as if it were this date, as YYYY-MM-DD or an
+    /// RFC 3339 timestamp. Overrides `SOURCE_DATE_EPOCH`.
+    #[clap(long, value_name = "DATE", value_parser = parse_date)]
+    pub date: Option<DateTime<FixedOffset>>,
+}
+
+impl Args {
+    /// What to do with files that already exist. Without a flag, nothing is overwritten.
+    pub fn conflict_policy(&self) -> ConflictPolicy {
+        if self.force {
+            ConflictPolicy::Overwrite
+        } else if self.skip_existing {
+            ConflictPolicy::Skip
+        } else if self.interactive {
+            ConflictPolicy::Ask
+        } else {
+            ConflictPolicy::Refuse
+        }
+    }
+
+    /// Whether to prefill the answers given the last time the template was used. Runs with
+    /// `--no-input` only use the values and defaults they are given, so that a script fails
+    /// on a missing variable instead of depending on what was typed in an earlier run.
+    pub fn uses_history(&self) -> bool {
+        !self.fresh && !self.no_input
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlanFormat {
+    Tree,
+    Json,
+}
+
+fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    let Some((key, value)) = s.split_once('=') else {
+        return Err(format!("expected KEY=VALUE, got `{s}`"));
+    };
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(format!("missing key in `{s}`"));
+    }
+    Ok((key.to_string(), value.to_string()))
 }
 
 const VERSION_MESSAGE: &str = concat!(
@@ -77,3 +167,32 @@ Config directory: {config_dir_path}
         "
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn history() {
+        let args = |flags: &[&str]| {
+            let argv = ["templatex", "report"].iter().chain(flags);
+            Cli::try_parse_from(argv).unwrap().args
+        };
+        assert!(args(&[]).uses_history());
+        assert!(!args(&["--fresh"]).uses_history());
+        assert!(!args(&["--no-input"]).uses_history());
+    }
+
+    #[test]
+    fn key_values() {
+        assert_eq!(
+            parse_key_value("title=A = B").unwrap(),
+            ("title".to_string(), "A = B".to_string())
+        );
+        assert_eq!(
+            parse_key_value("title=").unwrap(),
+            ("title".to_string(), String::new())
+        );
+        assert!(parse_key_value("title").is_err());
+        assert!(parse_key_value("=value").is_err());
+    }
+}
@@ -7,6 +7,7 @@ use std::{env, path::PathBuf, sync::LazyLock};
 use crate::{
     errors::Result,
     logging::{PROJECT_NAME, project_directory},
+    variables::Answers,
 };
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -14,6 +15,13 @@ pub struct Settings {
     #[serde(default)]
     pub(self) source_dirs: Vec<PathBuf>,
     pub(self) theme: Option<Theme>,
+    /// Values used for any template variable of the same name instead of the default the
+    /// template declares, e.g. `author` or `email`.
+    #[serde(default)]
+    pub(self) defaults: Answers,
+    /// A directory of partials and macros that every template can include or import as
+    /// `@shared/<path>`.
+    pub(self) shared_dir: Option<PathBuf>,
 }
 
 pub static CONFIG_FOLDER: LazyLock<Option<PathBuf>> = LazyLock::new(|| {
@@ -96,4 +104,10 @@ impl Settings {
     pub fn get_theme(&self) -> Option<Theme> {
         self.theme.clone()
     }
+    pub fn get_defaults(&self) -> Answers {
+        self.defaults.clone()
+    }
+    pub fn get_shared_dir(&self) -> Option<PathBuf> {
+        self.shared_dir.clone()
+    }
 }
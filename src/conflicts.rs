@@ -0,0 +1,295 @@
+use crate::{
+    errors::{Error, Result},
+    plan::{Action, Plan, SkipReason},
+    templating::{Engine, is_binary},
+};
+use color_eyre::eyre::eyre;
+use derive_more::Display;
+use inquire::Select;
+use ratatui::crossterm::style::Stylize;
+use std::fs;
+use tracing::{info, warn};
+
+/// What to do with files that already exist in the output directory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Don't write anything if any target exists.
+    #[default]
+    Refuse,
+    /// Replace existing files.
+    Overwrite,
+    /// Keep existing files and only write the new ones.
+    Skip,
+    /// Show a diff for each existing file and ask whether to keep, overwrite or merge it.
+    Ask,
+}
+
+/// Lines of context shown around each change in a diff.
+const CONTEXT_LINES: usize = 2;
+
+/// The size of the largest table [`diff`] builds to compare changed lines, about 16 MB.
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// Decides what happens to every entry of `plan` whose target already exists. Every file that
+/// is overwritten or kept is logged, so existing work never changes silently.
+pub fn resolve(engine: &Engine, plan: &mut Plan, policy: ConflictPolicy) -> Result<()> {
+    let conflicts = (0..plan.entries.len())
+        .filter(|&i| plan.entries[i].overwrite && plan.entries[i].target.is_some())
+        .collect::<Vec<_>>();
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+    if policy == ConflictPolicy::Refuse {
+        let paths = conflicts
+            .iter()
+            .filter_map(|&i| plan.entries[i].target.as_ref())
+            .map(|p| format!("\n  - {}", p.display()))
+            .collect::<String>();
+        return Err(Error::Other(eyre!(
+            "Refusing to overwrite existing files in {}:{}\nUse --force to overwrite them, \
+             --skip-existing to keep them, or --interactive to decide for each file.",
+            plan.out_dir.display(),
+            paths
+        )));
+    }
+    for i in conflicts {
+        let target = plan.entries[i].target.clone().unwrap_or_default();
+        let resolution = match policy {
+            ConflictPolicy::Refuse | ConflictPolicy::Overwrite => Resolution::Overwrite,
+            ConflictPolicy::Skip => Resolution::Keep,
+            ConflictPolicy::Ask => {
+                let new = engine.contents(plan, &plan.entries[i])?;
+                let existing = fs::read(plan.out_dir.join(&target))?;
+                if new == existing {
+                    info!("{} is unchanged", target.display());
+                    continue;
+                }
+                ask(&target.display().to_string(), &existing, &new)?
+            }
+        };
+        let entry = &mut plan.entries[i];
+        match resolution {
+            Resolution::Overwrite => warn!("Overwriting {}", target.display()),
+            Resolution::Keep => {
+                info!("Keeping existing {}", target.display());
+                entry.action = Action::Skip {
+                    reason: SkipReason::Exists,
+                };
+            }
+            Resolution::Merge => {
+                warn!("Merging into {}", target.display());
+                entry.action = Action::Merge;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+enum Resolution {
+    #[display("Keep the existing file")]
+    Keep,
+    #[display("Overwrite it")]
+    Overwrite,
+    #[display("Merge, marking conflicting lines")]
+    Merge,
+}
+
+/// Shows the diff between the existing and the new contents of `path` and asks what to do.
+fn ask(path: &str, existing: &[u8], new: &[u8]) -> Result<Resolution> {
+    let options = if is_binary(existing) || is_binary(new) {
+        println!(
+            "\n{}",
+            format!("{path} already exists and is binary").bold()
+        );
+        vec![Resolution::Keep, Resolution::Overwrite]
+    } else {
+        println!("\n{}", format!("{path} already exists:").bold());
+        print_diff(
+            &String::from_utf8_lossy(existing),
+            &String::from_utf8_lossy(new),
+        );
+        vec![Resolution::Keep, Resolution::Overwrite, Resolution::Merge]
+    };
+    Ok(Select::new(&format!("What should happen to {path}?"), options).prompt()?)
+}
+
+/// A line of a diff between two texts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLine<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// A line-based diff from `old` to `new`, using the longest common subsequence of lines.
+pub fn diff<'a>(old: &'a str, new: &'a str) -> Vec<DiffLine<'a>> {
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+    // Only the lines between the common start and end have to be compared.
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut lines = old[..prefix]
+        .iter()
+        .map(|line| DiffLine::Same(line))
+        .collect::<Vec<_>>();
+    lines.extend(changed_lines(
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    ));
+    lines.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|line| DiffLine::Same(line)),
+    );
+    lines
+}
+
+/// The diff between the lines `old` and `new`. If comparing every line of one with every line
+/// of the other would take more than [`MAX_DIFF_CELLS`], all of `old` is shown as removed and
+/// all of `new` as added.
+fn changed_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<DiffLine<'a>> {
+    if (old.len() + 1) * (new.len() + 1) > MAX_DIFF_CELLS {
+        let removed = old.iter().map(|line| DiffLine::Removed(line));
+        return removed
+            .chain(new.iter().map(|line| DiffLine::Added(line)))
+            .collect();
+    }
+    // common[i][j] is the length of the longest common subsequence of old[i..] and new[j..].
+    let mut common = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(DiffLine::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(DiffLine::Removed(old[i]));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(new[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+/// Prints the changed lines from `old` to `new`, with a little context around each change.
+fn print_diff(old: &str, new: &str) {
+    let lines = diff(old, new);
+    let near_change = |i: usize| {
+        let start = i.saturating_sub(CONTEXT_LINES);
+        let end = (i + CONTEXT_LINES + 1).min(lines.len());
+        lines[start..end]
+            .iter()
+            .any(|l| !matches!(l, DiffLine::Same(_)))
+    };
+    let mut skipped = false;
+    for (i, line) in lines.iter().enumerate() {
+        match line {
+            DiffLine::Same(line) if near_change(i) => println!("  {line}"),
+            DiffLine::Same(_) => {
+                if !skipped {
+                    println!("{}", "  ...".dim());
+                }
+                skipped = true;
+                continue;
+            }
+            DiffLine::Removed(line) => println!("{}", format!("- {line}").red()),
+            DiffLine::Added(line) => println!("{}", format!("+ {line}").green()),
+        }
+        skipped = false;
+    }
+}
+
+/// Merges `new` into `existing`. Lines that are the same in both are kept once, and every
+/// place where they differ is wrapped in conflict markers, the way git does.
+pub fn merge(existing: &str, new: &str) -> String {
+    let mut merged = String::new();
+    let (mut ours, mut theirs) = (Vec::new(), Vec::new());
+    let flush = |merged: &mut String, ours: &mut Vec<&str>, theirs: &mut Vec<&str>| {
+        if ours.is_empty() && theirs.is_empty() {
+            return;
+        }
+        merged.push_str("<<<<<<< existing\n");
+        for line in ours.drain(..) {
+            merged.push_str(line);
+            merged.push('\n');
+        }
+        merged.push_str("=======\n");
+        for line in theirs.drain(..) {
+            merged.push_str(line);
+            merged.push('\n');
+        }
+        merged.push_str(">>>>>>> template\n");
+    };
+    for line in diff(existing, new) {
+        match line {
+            DiffLine::Same(line) => {
+                flush(&mut merged, &mut ours, &mut theirs);
+                merged.push_str(line);
+                merged.push('\n');
+            }
+            DiffLine::Removed(line) => ours.push(line),
+            DiffLine::Added(line) => theirs.push(line),
+        }
+    }
+    flush(&mut merged, &mut ours, &mut theirs);
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test() {
+        let existing = "\\title{Draft}\n\\author{Me}\nMy notes\n";
+        let new = "\\title{Report}\n\\author{Me}\n";
+        assert_eq!(
+            diff(existing, new),
+            [
+                DiffLine::Removed("\\title{Draft}"),
+                DiffLine::Added("\\title{Report}"),
+                DiffLine::Same("\\author{Me}"),
+                DiffLine::Removed("My notes"),
+            ]
+        );
+        assert_eq!(
+            merge(existing, new),
+            "<<<<<<< existing\n\\title{Draft}\n=======\n\\title{Report}\n>>>>>>> template\n\
+             \\author{Me}\n<<<<<<< existing\nMy notes\n=======\n>>>>>>> template\n"
+        );
+        assert_eq!(merge(new, new), new);
+    }
+    #[test]
+    fn large_diffs() {
+        let old = (0..10_000)
+            .map(|i| format!("line {i}\n"))
+            .collect::<String>();
+        let new = old.replace("line 5000\n", "changed\n");
+        let lines = diff(&old, &new);
+        assert_eq!(lines.len(), 10_001);
+        assert_eq!(lines[5000], DiffLine::Removed("line 5000"));
+        assert_eq!(lines[5001], DiffLine::Added("changed"));
+        let new = old.replace("line", "entry");
+        let lines = diff(&old, &new);
+        assert_eq!(lines.len(), 20_000);
+        assert_eq!(lines[0], DiffLine::Removed("line 0"));
+        assert_eq!(lines[10_000], DiffLine::Added("entry 0"));
+    }
+}
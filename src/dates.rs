@@ -0,0 +1,290 @@
+use crate::{builtins::build_date, latex::ordinal};
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use std::collections::HashMap;
+use tera::{Tera, Value, try_get_value};
+
+/// The languages `date_format` knows month and weekday names for.
+pub const LOCALES: [&str; 4] = ["en", "de", "fr", "es"];
+
+const MONTHS: [[&str; 12]; 4] = [
+    [
+        "January",
+        "February",
+        "March",
+        "April",
+        "May",
+        "June",
+        "July",
+        "August",
+        "September",
+        "October",
+        "November",
+        "December",
+    ],
+    [
+        "Januar",
+        "Februar",
+        "März",
+        "April",
+        "Mai",
+        "Juni",
+        "Juli",
+        "August",
+        "September",
+        "Oktober",
+        "November",
+        "Dezember",
+    ],
+    [
+        "janvier",
+        "février",
+        "mars",
+        "avril",
+        "mai",
+        "juin",
+        "juillet",
+        "août",
+        "septembre",
+        "octobre",
+        "novembre",
+        "décembre",
+    ],
+    [
+        "enero",
+        "febrero",
+        "marzo",
+        "abril",
+        "mayo",
+        "junio",
+        "julio",
+        "agosto",
+        "septiembre",
+        "octubre",
+        "noviembre",
+        "diciembre",
+    ],
+];
+
+const SHORT_MONTHS: [[&str; 12]; 4] = [
+    [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ],
+    [
+        "Jan.", "Feb.", "März", "Apr.", "Mai", "Juni", "Juli", "Aug.", "Sept.", "Okt.", "Nov.",
+        "Dez.",
+    ],
+    [
+        "janv.", "févr.", "mars", "avr.", "mai", "juin", "juil.", "août", "sept.", "oct.", "nov.",
+        "déc.",
+    ],
+    [
+        "ene", "feb", "mar", "abr", "may", "jun", "jul", "ago", "sept", "oct", "nov", "dic",
+    ],
+];
+
+const WEEKDAYS: [[&str; 7]; 4] = [
+    [
+        "Monday",
+        "Tuesday",
+        "Wednesday",
+        "Thursday",
+        "Friday",
+        "Saturday",
+        "Sunday",
+    ],
+    [
+        "Montag",
+        "Dienstag",
+        "Mittwoch",
+        "Donnerstag",
+        "Freitag",
+        "Samstag",
+        "Sonntag",
+    ],
+    [
+        "lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche",
+    ],
+    [
+        "lunes",
+        "martes",
+        "miércoles",
+        "jueves",
+        "viernes",
+        "sábado",
+        "domingo",
+    ],
+];
+
+/// How much of a date `date_format` spells out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateStyle {
+    /// `10/18/2026`, `18.10.2026`
+    Short,
+    /// `Oct 18, 2026`, `18. Okt. 2026`
+    Medium,
+    /// `October 18th, 2026`, `18. Oktober 2026`
+    Long,
+    /// `Sunday, October 18th, 2026`, `Sonntag, 18. Oktober 2026`
+    Full,
+}
+
+impl DateStyle {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "short" => Some(Self::Short),
+            "medium" => Some(Self::Medium),
+            "long" => Some(Self::Long),
+            "full" => Some(Self::Full),
+            _ => None,
+        }
+    }
+}
+
+/// Spells out `date` in the language of `locale`, e.g. `en`, `de-AT` or `fr_CA`. Fails if
+/// the language isn't one of [`LOCALES`].
+pub fn format_date(date: NaiveDate, locale: &str, style: DateStyle) -> Result<String, String> {
+    let language = locale
+        .split(['-', '_'])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    let Some(lang) = LOCALES.iter().position(|l| *l == language) else {
+        return Err(format!(
+            "unknown locale `{locale}`, expected one of {}",
+            LOCALES.join(", ")
+        ));
+    };
+    let (day, year) = (date.day(), date.year());
+    let month = date.month0() as usize;
+    let weekday = WEEKDAYS[lang][date.weekday().num_days_from_monday() as usize];
+    let (short_month, long_month) = (SHORT_MONTHS[lang][month], MONTHS[lang][month]);
+    let long = match language.as_str() {
+        "en" => format!("{long_month} {}, {year}", ordinal(day.into())),
+        "de" => format!("{day}. {long_month} {year}"),
+        "fr" if day == 1 => format!("1er {long_month} {year}"),
+        "fr" => format!("{day} {long_month} {year}"),
+        _ => format!("{day} de {long_month} de {year}"),
+    };
+    Ok(match (style, language.as_str()) {
+        (DateStyle::Short, "en") => format!("{}/{day}/{year}", month + 1),
+        (DateStyle::Short, "de") => format!("{day:02}.{:02}.{year}", month + 1),
+        (DateStyle::Short, _) => format!("{day:02}/{:02}/{year}", month + 1),
+        (DateStyle::Medium, "en") => format!("{short_month} {day}, {year}"),
+        (DateStyle::Medium, "de") => format!("{day}. {short_month} {year}"),
+        (DateStyle::Medium, _) => format!("{day} {short_month} {year}"),
+        (DateStyle::Long, _) => long,
+        (DateStyle::Full, "fr") => format!("{weekday} {long}"),
+        (DateStyle::Full, _) => format!("{weekday}, {long}"),
+    })
+}
+
+/// Reads a date from a Tera value: a `YYYY-MM-DD` string, an RFC 3339 timestamp as returned
+/// by `now()`, or a Unix timestamp.
+fn date_of(value: &Value) -> Option<NaiveDate> {
+    match value {
+        Value::String(s) => NaiveDate::parse_from_str(s, "%Y-%m-%d")
+            .ok()
+            .or_else(|| Some(DateTime::parse_from_rfc3339(s).ok()?.date_naive())),
+        Value::Number(n) => Some(DateTime::<Utc>::from_timestamp(n.as_i64()?, 0)?.date_naive()),
+        _ => None,
+    }
+}
+
+/// Registers `now()` and the `date_format` filter on `tera`. `now()` returns the build date,
+/// see [`build_date`], so it can be pinned with `--date` or `SOURCE_DATE_EPOCH`.
+pub fn register(tera: &mut Tera) {
+    tera.register_function(
+        "now",
+        |args: &HashMap<String, Value>| -> tera::Result<Value> {
+            let mut date = build_date();
+            if let Some(utc) = args.get("utc")
+                && try_get_value!("now", "utc", bool, utc)
+            {
+                date = date.with_timezone(&Utc).fixed_offset();
+            }
+            if let Some(timestamp) = args.get("timestamp")
+                && try_get_value!("now", "timestamp", bool, timestamp)
+            {
+                return Ok(Value::from(date.timestamp()));
+            }
+            Ok(Value::String(date.to_rfc3339()))
+        },
+    );
+    tera.register_filter(
+        "date_format",
+        |value: &Value, args: &HashMap<String, Value>| -> tera::Result<Value> {
+            let Some(date) = date_of(value) else {
+                return Err(tera::Error::msg(format!(
+                    "Filter `date_format` was called on an incorrect value: got `{value}` but \
+                     expected a date"
+                )));
+            };
+            let locale = match args.get("locale") {
+                Some(locale) => try_get_value!("date_format", "locale", String, locale),
+                None => "en".to_string(),
+            };
+            let style = match args.get("style") {
+                Some(style) => {
+                    let style = try_get_value!("date_format", "style", String, style);
+                    DateStyle::parse(&style).ok_or_else(|| {
+                        tera::Error::msg(format!(
+                            "Filter `date_format` received an unknown style `{style}`, expected \
+                             short, medium, long or full"
+                        ))
+                    })?
+                }
+                None => DateStyle::Long,
+            };
+            let formatted = format_date(date, &locale, style)
+                .map_err(|e| tera::Error::msg(format!("Filter `date_format`: {e}")))?;
+            Ok(Value::String(formatted))
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test() {
+        let date = NaiveDate::from_ymd_opt(2026, 10, 18).unwrap();
+        let long = LOCALES.map(|l| format_date(date, l, DateStyle::Long).unwrap());
+        assert_eq!(
+            long,
+            [
+                "October 18th, 2026",
+                "18. Oktober 2026",
+                "18 octobre 2026",
+                "18 de octubre de 2026"
+            ]
+        );
+        assert_eq!(
+            format_date(date, "de-AT", DateStyle::Full).unwrap(),
+            "Sonntag, 18. Oktober 2026"
+        );
+        assert_eq!(
+            format_date(date, "en_US", DateStyle::Short).unwrap(),
+            "10/18/2026"
+        );
+        assert_eq!(
+            format_date(date, "es", DateStyle::Medium).unwrap(),
+            "18 oct 2026"
+        );
+        let first = NaiveDate::from_ymd_opt(2026, 3, 1).unwrap();
+        assert_eq!(
+            format_date(first, "fr", DateStyle::Full).unwrap(),
+            "dimanche 1er mars 2026"
+        );
+        assert!(format_date(date, "it", DateStyle::Long).is_err());
+
+        let mut tera = Tera::default();
+        register(&mut tera);
+        let rendered = tera
+            .render_str(
+                r#"<~{ "2026-10-18" | date_format(locale="de", style="short") }~>"#,
+                &tera::Context::new(),
+            )
+            .unwrap();
+        assert_eq!(rendered, "18.10.2026");
+    }
+}
@@ -0,0 +1,324 @@
+use std::collections::HashSet;
+use tera::{
+    Tera,
+    ast::{Expr, ExprVal, FunctionCall, Node},
+};
+
+/// Returns the free variables of template `name`, in order of first appearance.
+///
+/// Loop variables and `set` bindings are left out, and dotted or indexed paths such as
+/// `author.name` are reported by their root (`author`). Templates pulled in through `include`,
+/// `extends` and `import` are visited as well. Macro bodies are not, since macros can only read
+/// their arguments.
+pub fn template_variables(tera: &Tera, name: &str) -> Vec<String> {
+    let mut visitor = Visitor::new(tera);
+    visitor.visit_template(name);
+    visitor.found
+}
+
+/// Returns the 1-based lines of `source` on which `name` is used as the root of an
+/// identifier inside a `<~{ }~>` or `{% %}` tag.
+pub fn usage_lines(source: &str, name: &str) -> Vec<usize> {
+    let mut lines = Vec::new();
+    let mut rest = source;
+    let mut offset = 0;
+    while let Some((start, end_delim)) = next_tag(rest) {
+        let Some(len) = rest[start..].find(end_delim) else {
+            break;
+        };
+        let tag = &rest[start..start + len];
+        for (at, ident) in root_identifiers(tag) {
+            if ident == name {
+                let line = source[..offset + start + at].matches('\n').count() + 1;
+                if !lines.contains(&line) {
+                    lines.push(line);
+                }
+            }
+        }
+        offset += start + len;
+        rest = &rest[start + len..];
+    }
+    lines
+}
+
+/// Finds the start of the next tag in `source`, returning it with the matching end delimiter.
+fn next_tag(source: &str) -> Option<(usize, &'static str)> {
+    [("<~{", "}~>"), ("{%", "%}")]
+        .into_iter()
+        .filter_map(|(start, end)| source.find(start).map(|i| (i, end)))
+        .min_by_key(|(i, _)| *i)
+}
+
+/// Returns the identifiers in a tag that are not preceded by a `.`, skipping string literals.
+fn root_identifiers(tag: &str) -> Vec<(usize, &str)> {
+    let mut idents = Vec::new();
+    let mut chars = tag.char_indices().peekable();
+    let mut prev = None;
+    while let Some((i, c)) = chars.next() {
+        if matches!(c, '"' | '\'' | '`') {
+            for (_, inner) in chars.by_ref() {
+                if inner == c {
+                    break;
+                }
+            }
+        } else if c.is_alphabetic() || c == '_' {
+            let mut end = i + c.len_utf8();
+            while let Some(&(j, next)) = chars.peek() {
+                if !(next.is_alphanumeric() || next == '_') {
+                    break;
+                }
+                end = j + next.len_utf8();
+                chars.next();
+            }
+            if prev != Some('.') {
+                idents.push((i, &tag[i..end]));
+            }
+            prev = tag[..end].chars().next_back();
+            continue;
+        }
+        if !c.is_whitespace() {
+            prev = Some(c);
+        }
+    }
+    idents
+}
+
+struct Visitor<'a> {
+    tera: &'a Tera,
+    /// Names bound by enclosing `for` loops and `set` tags.
+    scopes: Vec<HashSet<String>>,
+    /// Templates already walked, so include/extends cycles terminate.
+    visited: HashSet<String>,
+    found: Vec<String>,
+}
+
+impl<'a> Visitor<'a> {
+    fn new(tera: &'a Tera) -> Self {
+        Self {
+            tera,
+            scopes: vec![HashSet::new()],
+            visited: HashSet::new(),
+            found: Vec::new(),
+        }
+    }
+
+    fn visit_template(&mut self, name: &str) {
+        if !self.visited.insert(name.to_string()) {
+            return;
+        }
+        let Ok(template) = self.tera.get_template(name) else {
+            return;
+        };
+        // Included templates see the includer's bindings, but their own `set`s stay local.
+        self.scopes.push(HashSet::new());
+        self.visit_nodes(&template.ast);
+        self.scopes.pop();
+    }
+
+    fn visit_nodes(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            self.visit_node(node);
+        }
+    }
+
+    fn visit_node(&mut self, node: &Node) {
+        match node {
+            Node::VariableBlock(_, expr) => self.visit_expr(expr),
+            // Macros can't see the template's context, so only their default arguments can
+            // read variables.
+            Node::MacroDefinition(_, definition, _) => {
+                for default in definition.args.values().flatten() {
+                    self.visit_expr(default);
+                }
+            }
+            Node::Extends(_, name) | Node::ImportMacro(_, name, _) => self.visit_template(name),
+            Node::Include(_, names, _) => {
+                for name in names {
+                    self.visit_template(name);
+                }
+            }
+            Node::Set(_, set) => {
+                self.visit_expr(&set.value);
+                let scope = if set.global {
+                    self.scopes.first_mut()
+                } else {
+                    self.scopes.last_mut()
+                };
+                if let Some(scope) = scope {
+                    scope.insert(set.key.clone());
+                }
+            }
+            Node::FilterSection(_, section, _) => {
+                self.visit_call(&section.filter);
+                self.visit_nodes(&section.body);
+            }
+            Node::Block(_, block, _) => self.visit_nodes(&block.body),
+            Node::Forloop(_, forloop, _) => {
+                self.visit_expr(&forloop.container);
+                let mut scope = HashSet::from(["loop".to_string(), forloop.value.clone()]);
+                scope.extend(forloop.key.clone());
+                self.scopes.push(scope);
+                self.visit_nodes(&forloop.body);
+                self.scopes.pop();
+                if let Some(empty_body) = &forloop.empty_body {
+                    self.visit_nodes(empty_body);
+                }
+            }
+            Node::If(if_node, _) => {
+                for (_, condition, body) in &if_node.conditions {
+                    self.visit_expr(condition);
+                    self.visit_nodes(body);
+                }
+                if let Some((_, body)) = &if_node.otherwise {
+                    self.visit_nodes(body);
+                }
+            }
+            Node::Super
+            | Node::Text(_)
+            | Node::Raw(..)
+            | Node::Break(_)
+            | Node::Continue(_)
+            | Node::Comment(..) => {}
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        self.visit_val(&expr.val);
+        for filter in &expr.filters {
+            self.visit_call(filter);
+        }
+    }
+
+    fn visit_call(&mut self, call: &FunctionCall) {
+        for arg in call.args.values() {
+            self.visit_expr(arg);
+        }
+    }
+
+    fn visit_val(&mut self, val: &ExprVal) {
+        match val {
+            ExprVal::Ident(ident) => self.visit_ident(ident),
+            ExprVal::Math(math) => {
+                self.visit_expr(&math.lhs);
+                self.visit_expr(&math.rhs);
+            }
+            ExprVal::Logic(logic) => {
+                self.visit_expr(&logic.lhs);
+                self.visit_expr(&logic.rhs);
+            }
+            ExprVal::Test(test) => {
+                self.visit_ident(&test.ident);
+                for arg in &test.args {
+                    self.visit_expr(arg);
+                }
+            }
+            ExprVal::MacroCall(call) => {
+                for arg in call.args.values() {
+                    self.visit_expr(arg);
+                }
+            }
+            ExprVal::FunctionCall(call) => self.visit_call(call),
+            ExprVal::Array(items) => {
+                for item in items {
+                    self.visit_expr(item);
+                }
+            }
+            ExprVal::StringConcat(concat) => {
+                for value in &concat.values {
+                    self.visit_val(value);
+                }
+            }
+            ExprVal::In(in_expr) => {
+                self.visit_expr(&in_expr.lhs);
+                self.visit_expr(&in_expr.rhs);
+            }
+            ExprVal::String(_) | ExprVal::Int(_) | ExprVal::Float(_) | ExprVal::Bool(_) => {}
+        }
+    }
+
+    /// Records the root of a (possibly dotted or indexed) identifier, along with any
+    /// identifiers used as indices, e.g. both `chapters` and `i` in `chapters[i].title`.
+    fn visit_ident(&mut self, ident: &str) {
+        let mut parts = ident.split(['[', ']']);
+        if let Some(root) = parts.next().and_then(|p| p.split('.').next()) {
+            self.record(root);
+        }
+        for index in parts.step_by(2) {
+            let index = index.trim();
+            let is_literal = index.starts_with(['"', '\'', '`'])
+                || index.chars().next().is_some_and(|c| c.is_ascii_digit());
+            if !index.is_empty() && !is_literal {
+                self.visit_ident(index);
+            }
+        }
+    }
+
+    fn record(&mut self, name: &str) {
+        if name.is_empty()
+            || name == "__tera_context"
+            || self.scopes.iter().any(|scope| scope.contains(name))
+            || self.found.iter().any(|f| f == name)
+        {
+            return;
+        }
+        self.found.push(name.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test() {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            (
+                "main.tex",
+                r#"{% import "macros.tex" as m %}
+\title{<~{ title | upper }~>}
+\author{<~{ author.name }~>}
+{% if twocolumn %}\twocolumn{% endif %}
+{% set short = title | truncate(length=subtitle_length) %}
+\markboth{<~{ short }~>}{}
+{% for pkg in packages %}\usepackage{<~{ pkg }~>}<~{ loop.index }~>{% endfor %}
+<~{ m::box(content=abstract) }~>
+<~{ chapters[current].title }~>
+<~{ now(format=date_format) }~>
+{% include "titlepage.tex" %}"#,
+            ),
+            (
+                "titlepage.tex",
+                "<~{ institution }~>{% include \"main.tex\" %}",
+            ),
+            (
+                "macros.tex",
+                "{% macro box(content) %}<~{ content }~><~{ boxcolor }~>{% endmacro %}",
+            ),
+        ])
+        .unwrap();
+        assert_eq!(
+            template_variables(&tera, "main.tex"),
+            [
+                "title",
+                "author",
+                "twocolumn",
+                "subtitle_length",
+                "packages",
+                "abstract",
+                "chapters",
+                "current",
+                "date_format",
+                "institution",
+            ]
+        );
+    }
+
+    #[test]
+    fn lines() {
+        let source = "\\title{<~{ title }~>}\n\\author{<~{ author.title }~>}\n\
+            {% if title and \"title\" in tags %}\n<~{ title | upper }~> title";
+        assert_eq!(usage_lines(source, "title"), [1, 3, 4]);
+        assert_eq!(usage_lines(source, "author"), [2]);
+        assert_eq!(usage_lines(source, "tags"), [3]);
+    }
+}
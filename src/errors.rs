@@ -1,5 +1,5 @@
 use derive_more::Display;
-use std::env;
+use std::{env, fmt, path::PathBuf};
 use thiserror::Error;
 use tracing::error;
 
@@ -11,15 +11,101 @@ pub enum Error {
     TeraError(#[from] tera::Error),
     IoError(#[from] std::io::Error),
     InputError(#[from] InputError),
+    InputErrors(#[from] InputErrors),
+    RenderErrors(#[from] RenderErrors),
+    PromptError(#[from] inquire::InquireError),
     PatternError(#[from] glob::PatternError),
     ConfigError(#[from] config::ConfigError),
     TemplateConfigError(#[from] toml::de::Error),
+    JsonError(#[from] serde_json::Error),
     Other(#[from] color_eyre::Report),
 }
 
 #[derive(Error, Debug)]
-#[error("Invalid input for field {0}. This may be due to a typo or a missing value.")]
-pub struct InputError(String);
+#[error("Invalid input for field {field}. {reason}")]
+pub struct InputError {
+    field: String,
+    reason: String,
+}
+
+impl InputError {
+    pub fn new(field: impl Into<String>) -> Self {
+        Self::with_reason(field, "This may be due to a typo or a missing value.")
+    }
+    pub fn with_reason(field: impl Into<String>, reason: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            reason: reason.into(),
+        }
+    }
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+/// Every invalid or missing value found while checking a set of answers.
+#[derive(Error, Debug)]
+pub struct InputErrors(pub Vec<InputError>);
+
+impl fmt::Display for InputErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid input for {} field(s):", self.0.len())?;
+        for err in &self.0 {
+            write!(f, "\n  - {}: {}", err.field, err.reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// A file that couldn't be generated, with the reason.
+#[derive(Error, Debug)]
+#[error("{}: {message}", path.display())]
+pub struct RenderError {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl RenderError {
+    pub fn new(path: impl Into<PathBuf>, err: &Error) -> Self {
+        let message = match err {
+            Error::TeraError(e) => error_chain(e),
+            e => e.to_string(),
+        };
+        Self {
+            path: path.into(),
+            message,
+        }
+    }
+}
+
+/// Every file that failed while generating a project.
+#[derive(Error, Debug)]
+pub struct RenderErrors(pub Vec<RenderError>);
+
+impl fmt::Display for RenderErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to generate {} file(s):", self.0.len())?;
+        for err in &self.0 {
+            write!(f, "\n  - {err}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats an error followed by all of its sources, e.g. a Tera error and the reason behind it.
+pub fn error_chain(err: &dyn std::error::Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        message.push_str(": ");
+        message.push_str(&err.to_string());
+        source = err.source();
+    }
+    message
+}
 
 pub fn init() -> color_eyre::Result<()> {
     let (panic_hook, eyre_hook) = color_eyre::config::HookBuilder::default()
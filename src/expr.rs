@@ -0,0 +1,105 @@
+use crate::{
+    discovery,
+    errors::{Error, Result, error_chain},
+};
+use color_eyre::eyre::eyre;
+use tera::{Context, Tera, Value};
+
+/// Name of the one-off template used to inspect expressions.
+const EXPR_TEMPLATE: &str = "__templatex_expr";
+
+/// Returns true if `s` contains Tera tags and has to be rendered rather than used literally.
+pub fn is_template(s: &str) -> bool {
+    s.contains("<~{") || s.contains("{%")
+}
+
+/// Wraps an expression so that rendering it yields its value as JSON.
+fn json_template(expr: &str) -> String {
+    format!(
+        "{{% set __templatex_value = {expr} %}}<~{{ __templatex_value | json_encode() | safe }}~>"
+    )
+}
+
+/// Evaluates the Tera expression `expr` against `context`, keeping the type of the result.
+pub fn evaluate(tera: &mut Tera, expr: &str, context: &Context) -> Result<Value> {
+    let rendered = tera
+        .render_str(&json_template(expr), context)
+        .map_err(|e| Error::Other(eyre!("Failed to evaluate `{expr}`: {}", error_chain(&e))))?;
+    Ok(serde_json::from_str(&rendered)?)
+}
+
+/// Tells whether the Tera expression `expr` is true for `context`, by rendering it in an `if`
+/// tag. Variables that aren't in `context`, e.g. because their question was skipped, are false.
+pub fn is_true(tera: &mut Tera, expr: &str, context: &Context) -> Result<bool> {
+    let rendered = tera
+        .render_str(&format!("{{% if {expr} %}}true{{% endif %}}"), context)
+        .map_err(|e| Error::Other(eyre!("Failed to evaluate `{expr}`: {}", error_chain(&e))))?;
+    Ok(rendered == "true")
+}
+
+/// Renders the template string `template` against `context`.
+pub fn render(tera: &mut Tera, template: &str, context: &Context) -> Result<String> {
+    tera.render_str(template, context)
+        .map_err(|e| Error::Other(eyre!("Failed to render `{template}`: {}", error_chain(&e))))
+}
+
+/// Returns the variables the expression `expr` reads.
+pub fn dependencies(expr: &str) -> Result<Vec<String>> {
+    template_dependencies(&json_template(expr))
+}
+
+/// Returns the variables the template string `template` reads.
+pub fn template_dependencies(template: &str) -> Result<Vec<String>> {
+    let mut tera = Tera::default();
+    tera.add_raw_template(EXPR_TEMPLATE, template)?;
+    Ok(discovery::template_variables(&tera, EXPR_TEMPLATE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test() {
+        let mut tera = Tera::default();
+        let mut context = Context::new();
+        context.insert("title", "Hello World");
+        context.insert("date", "2026-10-18");
+        assert_eq!(
+            evaluate(&mut tera, "title | slugify", &context).unwrap(),
+            Value::String("hello-world".into())
+        );
+        assert_eq!(
+            evaluate(
+                &mut tera,
+                "date | truncate(length=4, end='') | int",
+                &context
+            )
+            .unwrap(),
+            Value::from(2026)
+        );
+        assert_eq!(
+            evaluate(&mut tera, "title | split(pat=' ')", &context).unwrap(),
+            tera::to_value(["Hello", "World"]).unwrap()
+        );
+        assert!(evaluate(&mut tera, "missing", &context).is_err());
+        assert!(!is_true(&mut tera, "missing", &context).unwrap());
+        assert!(is_true(&mut tera, "not missing and title", &context).unwrap());
+        assert!(
+            is_true(
+                &mut tera,
+                "title and date is starting_with('2026')",
+                &context
+            )
+            .unwrap()
+        );
+        assert!(!is_true(&mut tera, "title | split(pat=' ') | length > 2", &context).unwrap());
+        assert_eq!(
+            dependencies("title ~ ' ' ~ subtitle | default(value=short)").unwrap(),
+            ["title", "subtitle", "short"]
+        );
+        assert_eq!(
+            template_dependencies("<~{ title }~> ({{ year }})").unwrap(),
+            ["title"]
+        );
+    }
+}
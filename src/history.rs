@@ -0,0 +1,50 @@
+use crate::{errors::Result, logging::get_data_dir, variables::Answers};
+use std::{fs, io::ErrorKind, path::PathBuf};
+
+/// The directory, inside the data directory, that holds the remembered answers.
+const HISTORY_DIR: &str = "answers";
+
+/// The file the answers for `template` are remembered in.
+pub fn history_file(template: &str) -> PathBuf {
+    let file_name = template
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    get_data_dir()
+        .join(HISTORY_DIR)
+        .join(format!("{file_name}.json"))
+}
+
+/// The answers given the last time `template` was used, or nothing if it hasn't been used yet.
+pub fn load(template: &str) -> Result<Answers> {
+    match fs::read_to_string(history_file(template)) {
+        Ok(contents) => Ok(serde_json::from_str(&contents)?),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Answers::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Remembers `answers` as the latest answers for `template`.
+pub fn save(template: &str, answers: &Answers) -> Result<()> {
+    let path = history_file(template);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(answers)?)?;
+    Ok(())
+}
+
+/// Forgets the answers for `template`. Returns false if there were none.
+pub fn clear(template: &str) -> Result<bool> {
+    match fs::remove_file(history_file(template)) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
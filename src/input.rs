@@ -1 +1,385 @@
+use crate::{
+    builtins::Builtins,
+    errors::{InputError, InputErrors, Result},
+    templating::Template,
+    variables::{Answers, VariableConfig, VariableType, display_value, resolution_order},
+};
+use inquire::{
+    Confirm, CustomType, CustomUserError, Editor, MultiSelect, Select, Text,
+    list_option::ListOption, required, validator::Validation,
+};
+use std::{ffi::OsStr, fs, path::Path};
+use tera::{Context, Tera, Value};
+use tracing::debug;
 
+/// Reads variable values from a TOML file, or a JSON file if it has a `.json` extension.
+pub fn read_answers_file(path: &Path) -> Result<Answers> {
+    let contents = fs::read_to_string(path)?;
+    let answers = match path.extension().and_then(OsStr::to_str) {
+        Some("json") => serde_json::from_str(&contents)?,
+        _ => toml::from_str(&contents)?,
+    };
+    Ok(answers)
+}
+
+/// The values given on the command line: those in the answers file at `path`, if any, with
+/// the `KEY=VALUE` pairs of `--set` taking precedence.
+pub fn provided_answers(path: Option<&Path>, set: &[(String, String)]) -> Result<Answers> {
+    let mut provided = match path {
+        Some(path) => read_answers_file(path)?,
+        None => Answers::new(),
+    };
+    for (k, v) in set {
+        provided.insert(k.clone(), Value::String(v.clone()));
+    }
+    Ok(provided)
+}
+
+/// Resolves a value for each variable of `template`, in dependency order. Values in `provided`
+/// are used as-is (converted to the declared type), variables whose `when` condition is false
+/// get their fallback or are left out, computed variables are evaluated, and everything else
+/// is prompted for with its default prefilled. A value in `defaults`, e.g. a user default or
+/// the answer given the last time the template was used, replaces the declared default.
+/// With `no_input`, defaults are used instead of prompting and variables left without a
+/// value are reported as an error.
+///
+/// Provided values and defaults used without prompting are checked against the variable's
+/// `validate` rules, and must not be empty if it is `required`. Every invalid or missing value
+/// is reported in a single error.
+///
+/// Computed values and defaults can read both earlier answers and `builtins`.
+pub fn collect_answers(
+    template: &Template,
+    builtins: &Builtins,
+    provided: &Answers,
+    defaults: &Answers,
+    no_input: bool,
+) -> Result<Answers> {
+    let config = template.config();
+    let declared = &config.variables;
+    let mut tera = template.tera();
+    let mut answers = Answers::new();
+    let mut errors = Vec::new();
+    for name in resolution_order(&template.variable_names(), declared)? {
+        let var_config = declared.get(&name).cloned().unwrap_or_default();
+        let mut context = Context::from_serialize(&answers)?;
+        builtins.insert_into(&mut context);
+        let value = if let Some(value) = provided.get(&name) {
+            if !declared.contains_key(&name) {
+                answers.insert(name, value.clone());
+                continue;
+            }
+            let checked = var_config.coerce(&name, value.clone()).and_then(|v| {
+                var_config
+                    .validate(&name, &v, &mut tera, &context)
+                    .map(|_| v)
+            });
+            match checked {
+                Ok(value) => value,
+                Err(err) => {
+                    errors.push(err);
+                    // Keep the raw value so that later variables can still be resolved.
+                    answers.insert(name, value.clone());
+                    continue;
+                }
+            }
+        } else if !var_config.is_asked(&mut tera, &context)? {
+            let Some(fallback) = var_config.fallback_value(&name)? else {
+                debug!("Skipping {}: its condition is false", name);
+                continue;
+            };
+            fallback
+        } else if let Some(value) = var_config.compute_value(&name, &mut tera, &context)? {
+            value
+        } else {
+            // Defaults that don't fit the variable's type are ignored.
+            let preset = defaults
+                .get(&name)
+                .and_then(|value| var_config.coerce(&name, value.clone()).ok());
+            let default = match preset {
+                Some(preset) => Some(preset),
+                None => var_config.resolve_default(&name, &mut tera, &context)?,
+            };
+            if no_input {
+                let Some(default) = default else {
+                    errors.push(InputError::with_reason(&name, "No value given."));
+                    continue;
+                };
+                if let Err(err) = var_config.validate(&name, &default, &mut tera, &context) {
+                    errors.push(err);
+                }
+                default
+            } else {
+                prompt_variable(&name, &var_config, default, &tera, &context)?
+            }
+        };
+        answers.insert(name, value);
+    }
+    if !errors.is_empty() {
+        return Err(InputErrors(errors).into());
+    }
+    for (name, value) in provided {
+        if !answers.contains_key(name) {
+            answers.insert(name.clone(), value.clone());
+        }
+    }
+    Ok(answers)
+}
+
+/// Prompts for a single variable using the inquire widget that matches its type. Answers are
+/// checked against the variable's `validate` rules, evaluated with `tera` against `context`,
+/// and asked for again until they pass.
+pub fn prompt_variable(
+    name: &str,
+    config: &VariableConfig,
+    default: Option<Value>,
+    tera: &Tera,
+    context: &Context,
+) -> Result<Value> {
+    let check = {
+        let (name, config, tera, context) = (
+            name.to_string(),
+            config.clone(),
+            tera.clone(),
+            context.clone(),
+        );
+        move |value: &Value| -> std::result::Result<Validation, CustomUserError> {
+            let mut tera = tera.clone();
+            Ok(match config.validate(&name, value, &mut tera, &context) {
+                Ok(()) => Validation::Valid,
+                Err(err) => Validation::Invalid(err.reason().into()),
+            })
+        }
+    };
+    let label = config.label(name);
+    let help = config.help.as_deref();
+    let value = match config.kind {
+        VariableType::String => {
+            let default = default.as_ref().map(display_value);
+            let mut prompt = Text::new(&label);
+            if let Some(help) = help {
+                prompt = prompt.with_help_message(help);
+            }
+            if let Some(default) = default.as_deref() {
+                prompt = prompt.with_default(default);
+            }
+            if config.required {
+                prompt = prompt.with_validator(required!());
+            }
+            prompt = prompt.with_validator(move |s: &str| check(&Value::from(s)));
+            Value::String(prompt.prompt()?)
+        }
+        VariableType::Multiline => {
+            let default = default.as_ref().map(display_value);
+            let mut prompt = Editor::new(&label).with_file_extension(".tex");
+            if let Some(help) = help {
+                prompt = prompt.with_help_message(help);
+            }
+            if let Some(default) = default.as_deref() {
+                prompt = prompt.with_predefined_text(default);
+            }
+            if config.required {
+                prompt = prompt.with_validator(required!());
+            }
+            prompt = prompt.with_validator(move |s: &str| check(&Value::from(s)));
+            Value::String(prompt.prompt()?)
+        }
+        VariableType::Bool => {
+            let mut prompt = Confirm::new(&label);
+            if let Some(help) = help {
+                prompt = prompt.with_help_message(help);
+            }
+            if let Some(Value::Bool(default)) = default {
+                prompt = prompt.with_default(default);
+            }
+            Value::Bool(prompt.prompt()?)
+        }
+        VariableType::Int => {
+            let mut prompt =
+                CustomType::<i64>::new(&label).with_error_message("Please enter a whole number");
+            if let Some(help) = help {
+                prompt = prompt.with_help_message(help);
+            }
+            if let Some(default) = default.as_ref().and_then(Value::as_i64) {
+                prompt = prompt.with_default(default);
+            }
+            prompt = prompt.with_validator(move |n: &i64| check(&Value::from(*n)));
+            prompt.prompt()?.into()
+        }
+        VariableType::Float => {
+            let mut prompt =
+                CustomType::<f64>::new(&label).with_error_message("Please enter a number");
+            if let Some(help) = help {
+                prompt = prompt.with_help_message(help);
+            }
+            if let Some(default) = default.as_ref().and_then(Value::as_f64) {
+                prompt = prompt.with_default(default);
+            }
+            prompt = prompt.with_validator(move |n: &f64| check(&Value::from(*n)));
+            prompt.prompt()?.into()
+        }
+        VariableType::Choice => {
+            if config.choices.is_empty() {
+                return Err(InputError::new(name).into());
+            }
+            let cursor = default
+                .as_ref()
+                .and_then(Value::as_str)
+                .and_then(|d| config.choices.iter().position(|c| c == d))
+                .unwrap_or_default();
+            let mut prompt =
+                Select::new(&label, config.choices.clone()).with_starting_cursor(cursor);
+            if let Some(help) = help {
+                prompt = prompt.with_help_message(help);
+            }
+            Value::String(prompt.prompt()?)
+        }
+        VariableType::List if !config.choices.is_empty() => {
+            let selected = default
+                .as_ref()
+                .and_then(Value::as_array)
+                .map(|items| {
+                    items
+                        .iter()
+                        .filter_map(|item| {
+                            config
+                                .choices
+                                .iter()
+                                .position(|c| Some(c.as_str()) == item.as_str())
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            let mut prompt = MultiSelect::new(&label, config.choices.clone())
+                .with_default(&selected)
+                .with_validator(move |options: &[ListOption<&String>]| {
+                    let items = options.iter().map(|o| Value::from(o.value.as_str()));
+                    check(&Value::Array(items.collect()))
+                });
+            if let Some(help) = help {
+                prompt = prompt.with_help_message(help);
+            }
+            tera::to_value(prompt.prompt()?).map_err(|_| InputError::new(name))?
+        }
+        VariableType::List => {
+            let default = default.as_ref().map(display_value);
+            let help = help.unwrap_or("Separate entries with commas");
+            let mut prompt = Text::new(&label).with_help_message(help);
+            if let Some(default) = default.as_deref() {
+                prompt = prompt.with_default(default);
+            }
+            if config.required {
+                prompt = prompt.with_validator(required!());
+            }
+            let parse_config = config.clone();
+            let parse_name = name.to_string();
+            prompt =
+                prompt.with_validator(move |s: &str| match parse_config.parse(&parse_name, s) {
+                    Ok(value) => check(&value),
+                    Err(err) => Ok(Validation::Invalid(err.reason().into())),
+                });
+            config.parse(name, &prompt.prompt()?)?
+        }
+    };
+    // `Confirm` and `Select` take no validators, so their answers are checked afterwards.
+    if matches!(config.kind, VariableType::Bool | VariableType::Choice)
+        && let Err(err) = config.validate(name, &value, &mut tera.clone(), context)
+    {
+        eprintln!("{}", err);
+        return prompt_variable(name, config, Some(value), tera, context);
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{errors::Error, templating::EngineBuilder};
+
+    /// Collects the answers for a template configured by `config` without prompting, the way
+    /// `--no-input` does.
+    fn collect(config: &str, provided: &Answers) -> Result<Answers> {
+        let dir = tempfile::tempdir()?;
+        let template_dir = dir.path().join("article");
+        fs::create_dir_all(&template_dir)?;
+        fs::write(template_dir.join("templatex.toml"), config)?;
+        let engine = EngineBuilder::default()
+            .template_dirs(vec![template_dir])
+            .clone()
+            .build()?;
+        let template = engine.get_template("article").unwrap();
+        let builtins = template.builtins(&dir.path().join("out"));
+        collect_answers(template, &builtins, provided, &Answers::new(), true)
+    }
+
+    /// The variables `result` reports as invalid or missing.
+    fn invalid_fields(result: Result<Answers>) -> Vec<String> {
+        match result {
+            Err(Error::InputErrors(InputErrors(errors))) => {
+                errors.iter().map(|e| e.field().to_string()).collect()
+            }
+            result => panic!("expected invalid input, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn no_input() {
+        let config = r#"
+            [variables.title]
+            [variables.author]
+            [variables.year]
+            type = "int"
+            default = 2026
+        "#;
+        assert_eq!(
+            invalid_fields(collect(config, &Answers::new())),
+            ["title", "author"]
+        );
+        let provided = Answers::from([
+            ("title".to_string(), Value::from("Report")),
+            ("author".to_string(), Value::from("Ada")),
+        ]);
+        let answers = collect(config, &provided).unwrap();
+        assert_eq!(answers["year"], Value::from(2026));
+    }
+
+    #[test]
+    fn answers_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let toml = dir.path().join("answers.toml");
+        fs::write(&toml, "title = \"From TOML\"\nyear = 2026\n").unwrap();
+        let json = dir.path().join("answers.json");
+        fs::write(&json, r#"{"title": "From JSON", "draft": true}"#).unwrap();
+        let answers = read_answers_file(&toml).unwrap();
+        assert_eq!(answers["title"], Value::from("From TOML"));
+        assert_eq!(answers["year"], Value::from(2026));
+        let answers = read_answers_file(&json).unwrap();
+        assert_eq!(answers["draft"], Value::from(true));
+        let set = [("title".to_string(), "From --set".to_string())];
+        let provided = provided_answers(Some(&toml), &set).unwrap();
+        assert_eq!(provided["title"], Value::from("From --set"));
+        assert_eq!(provided["year"], Value::from(2026));
+    }
+
+    #[test]
+    fn required() {
+        let config = r#"
+            [variables.title]
+            required = true
+            [variables.author]
+            required = true
+            default = ""
+            [variables.keywords]
+            type = "list"
+            required = true
+        "#;
+        let provided = Answers::from([
+            ("title".to_string(), Value::from(" ")),
+            ("keywords".to_string(), Value::from("")),
+        ]);
+        assert_eq!(
+            invalid_fields(collect(config, &provided)),
+            ["title", "author", "keywords"]
+        );
+    }
+}
@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+use tera::{Tera, Value, try_get_value};
+
+/// Extensions of the files whose values are escaped by default.
+pub const ESCAPED_EXTENSIONS: [&str; 4] = [".tex", ".sty", ".cls", ".bib"];
+
+/// Accented letters by the LaTeX accent command that produces them, along with their base
+/// letters in the same order.
+const ACCENTS: [(&str, &str, &str); 13] = [
+    ("`", "àèìòùÀÈÌÒÙ", "aeiouAEIOU"),
+    ("'", "áéíóúýćńśźĺŕÁÉÍÓÚÝĆŃŚŹĹŔ", "aeiouycnszlrAEIOUYCNSZLR"),
+    ("^", "âêîôûĉĝĥĵŝŵŷÂÊÎÔÛĈĜĤĴŜŴŶ", "aeiouceghjswyAEIOUCGHJSWY"),
+    ("~", "ãñõĩũÃÑÕĨŨ", "anoiuANOIU"),
+    ("\"", "äëïöüÿÄËÏÖÜŸ", "aeiouyAEIOUY"),
+    ("=", "āēīōūĀĒĪŌŪ", "aeiouAEIOU"),
+    (".", "żėġŻĖĠİ", "zegZEGI"),
+    ("u", "ăğŭĂĞŬ", "aguAGU"),
+    ("r", "åůÅŮ", "auAU"),
+    ("c", "çşţģķļņÇŞŢĢĶĻŅ", "cstgklnCSTGKLN"),
+    ("v", "čďěňřšťžČĎĚŇŘŠŤŽ", "cdenrstzCDENRSTZ"),
+    ("H", "őűŐŰ", "ouOU"),
+    ("k", "ąęįųĄĘĮŲ", "aeiuAEIU"),
+];
+
+/// Letters that LaTeX has a command for, with their closest ASCII spelling.
+const LETTERS: [(char, &str, &str); 13] = [
+    ('ß', "\\ss{}", "ss"),
+    ('æ', "\\ae{}", "ae"),
+    ('Æ', "\\AE{}", "AE"),
+    ('œ', "\\oe{}", "oe"),
+    ('Œ', "\\OE{}", "OE"),
+    ('ø', "\\o{}", "o"),
+    ('Ø', "\\O{}", "O"),
+    ('ł', "\\l{}", "l"),
+    ('Ł', "\\L{}", "L"),
+    ('ı', "\\i{}", "i"),
+    ('đ', "\\dj{}", "d"),
+    ('Đ', "\\DJ{}", "D"),
+    ('þ', "\\th{}", "th"),
+];
+
+/// Typographic characters and the LaTeX input that produces them.
+const SYMBOLS: [(char, &str); 10] = [
+    ('–', "--"),
+    ('—', "---"),
+    ('‘', "`"),
+    ('’', "'"),
+    ('“', "``"),
+    ('”', "''"),
+    ('…', "\\ldots{}"),
+    ('€', "\\texteuro{}"),
+    ('§', "\\S{}"),
+    ('\u{a0}', "~"),
+];
+
+/// Words that stay lowercase in titles, unless they start or end the title or a subtitle.
+const SMALL_WORDS: [&str; 22] = [
+    "a", "an", "and", "as", "at", "but", "by", "en", "for", "from", "if", "in", "into", "nor",
+    "of", "on", "or", "per", "the", "to", "via", "with",
+];
+
+/// Escapes the ten characters that have a special meaning in LaTeX, so that `input` is typeset
+/// as written. Used by Tera to escape every value printed into a LaTeX file, unless it is
+/// marked `| safe`.
+pub fn escape(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The accent command and base letter of `c`, e.g. `("'", 'e')` for `é`.
+fn accent_of(c: char) -> Option<(&'static str, char)> {
+    ACCENTS.iter().find_map(|(command, accented, bases)| {
+        let i = accented.chars().position(|a| a == c)?;
+        Some((*command, bases.chars().nth(i)?))
+    })
+}
+
+/// Escapes `input` like [`escape`], and spells accented letters and typographic characters
+/// with LaTeX commands, e.g. `é` as `\'e`, for templates compiled with pdfLaTeX.
+pub fn unicode_to_latex(input: &str) -> String {
+    let mut latex = String::with_capacity(input.len());
+    for c in input.chars() {
+        if let Some((command, base)) = accent_of(c) {
+            // Accents named by a letter need braces, the others can be written directly.
+            if command.chars().all(char::is_alphabetic) {
+                latex.push_str(&format!("\\{command}{{{base}}}"));
+            } else {
+                latex.push_str(&format!("\\{command}{base}"));
+            }
+        } else if let Some((_, command, _)) = LETTERS.iter().find(|(l, ..)| *l == c) {
+            latex.push_str(command);
+        } else if let Some((_, command)) = SYMBOLS.iter().find(|(s, _)| *s == c) {
+            latex.push_str(command);
+        } else {
+            latex.push_str(&escape(c.encode_utf8(&mut [0; 4])));
+        }
+    }
+    latex
+}
+
+/// Spells `input` with ASCII letters where possible, e.g. `Gödel` as `Godel`.
+fn to_ascii(input: &str) -> String {
+    input
+        .chars()
+        .map(|c| {
+            if let Some((_, base)) = accent_of(c) {
+                base.to_string()
+            } else if let Some((.., ascii)) = LETTERS.iter().find(|(l, ..)| *l == c) {
+                ascii.to_string()
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+
+/// Lowercase ASCII letters and digits of `input`, with every other run of characters turned
+/// into a single `-`. Only used for citation keys: templates get Tera's built-in `slugify`,
+/// which also transliterates scripts this module has no table for.
+fn slugify(input: &str) -> String {
+    to_ascii(input)
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// A citation key like `knuth1984texbook`: the family name of the first author, the year and
+/// the first word of the title that isn't a small word.
+pub fn bibkey(title: &str, author: &str, year: Option<&str>) -> String {
+    let first_author = author.split(" and ").next().unwrap_or_default();
+    let family_name = match first_author.split_once(',') {
+        Some((family, _)) => family,
+        None => first_author.split_whitespace().last().unwrap_or_default(),
+    };
+    let word = title
+        .split_whitespace()
+        .map(slugify)
+        .find(|word| !word.is_empty() && !SMALL_WORDS.contains(&word.as_str()))
+        .unwrap_or_default();
+    [family_name, year.unwrap_or_default(), &word]
+        .iter()
+        .map(|part| slugify(part).replace('-', ""))
+        .collect()
+}
+
+/// The initials of every name in `name`, e.g. `D. E. K.` for `Donald Ervin Knuth`. Hyphenated
+/// names keep their hyphen, as in `J.-P.`.
+pub fn initials(name: &str) -> String {
+    name.split_whitespace()
+        .filter_map(|word| {
+            let parts = word
+                .split('-')
+                .filter_map(|part| part.chars().find(|c| c.is_alphabetic()))
+                .map(|c| format!("{}.", c.to_uppercase()))
+                .collect::<Vec<_>>();
+            (!parts.is_empty()).then(|| parts.join("-"))
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Capitalizes every word of `title` except small words like `of` and `the`, which stay
+/// lowercase unless they start or end the title or follow a colon. Words that already contain
+/// a capital letter after their first letter, like `LaTeX`, are left alone.
+pub fn title_case(title: &str) -> String {
+    let words = title.split(' ').collect::<Vec<_>>();
+    let mut capitalize_next = true;
+    let mut result = Vec::with_capacity(words.len());
+    for (i, word) in words.iter().enumerate() {
+        let last = i + 1 == words.len();
+        let lower = word.to_lowercase();
+        let cased = if word.chars().skip(1).any(char::is_uppercase) {
+            word.to_string()
+        } else if !capitalize_next && !last && SMALL_WORDS.contains(&lower.as_str()) {
+            lower
+        } else {
+            lower
+                .split('-')
+                .map(capitalize)
+                .collect::<Vec<_>>()
+                .join("-")
+        };
+        capitalize_next = word.ends_with(':') || word.is_empty() && capitalize_next;
+        result.push(cased);
+    }
+    result.join(" ")
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// `path` in the form TeX expects in `\input` and `\includegraphics`: with forward slashes,
+/// without a leading `./`, and in quotes if it contains spaces.
+pub fn texpath(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let path = path.trim_start_matches("./");
+    if path.contains(' ') {
+        format!("\"{path}\"")
+    } else {
+        path.to_string()
+    }
+}
+
+/// `n` with its English ordinal suffix, e.g. `1st`, `12th` or `23rd`.
+pub fn ordinal(n: i64) -> String {
+    let suffix = match (n.abs() % 10, n.abs() % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{n}{suffix}")
+}
+
+/// Registers the LaTeX filters on `tera`. Filters that produce LaTeX, like `unicode_to_latex`
+/// and `texpath`, need `| safe` in escaped files so that their output isn't escaped again.
+pub fn register_filters(tera: &mut Tera) {
+    fn string_filter(
+        name: &'static str,
+        f: fn(&str) -> String,
+    ) -> impl Fn(&Value, &HashMap<String, Value>) -> tera::Result<Value> {
+        move |value, _| {
+            let s = try_get_value!(name, "value", String, value);
+            Ok(Value::String(f(&s)))
+        }
+    }
+    tera.register_filter("latex_escape", string_filter("latex_escape", escape));
+    tera.register_filter(
+        "unicode_to_latex",
+        string_filter("unicode_to_latex", unicode_to_latex),
+    );
+    tera.register_filter("initials", string_filter("initials", initials));
+    tera.register_filter("title_case", string_filter("title_case", title_case));
+    tera.register_filter("texpath", string_filter("texpath", texpath));
+    tera.register_filter(
+        "bibkey",
+        |value: &Value, args: &HashMap<String, Value>| -> tera::Result<Value> {
+            let title = try_get_value!("bibkey", "value", String, value);
+            let author = match args.get("author") {
+                Some(author) => try_get_value!("bibkey", "author", String, author),
+                None => return Err(tera::Error::msg("Filter `bibkey` expected an `author` arg")),
+            };
+            let year = args.get("year").map(|year| match year {
+                Value::String(year) => year.clone(),
+                year => year.to_string(),
+            });
+            Ok(Value::String(bibkey(&title, &author, year.as_deref())))
+        },
+    );
+    tera.register_filter(
+        "ordinal",
+        |value: &Value, _: &HashMap<String, Value>| -> tera::Result<Value> {
+            let n = match value {
+                Value::String(s) => s.trim().parse::<i64>().ok(),
+                value => value.as_i64(),
+            };
+            match n {
+                Some(n) => Ok(Value::String(ordinal(n))),
+                None => Err(tera::Error::msg(format!(
+                    "Filter `ordinal` was called on an incorrect value: got `{value}` but \
+                     expected a whole number"
+                ))),
+            }
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test() {
+        assert_eq!(escape("Smith & Jones"), "Smith \\& Jones");
+        assert_eq!(
+            escape("50% of $x_1$ {#} ~^\\"),
+            "50\\% of \\$x\\_1\\$ \\{\\#\\} \\textasciitilde{}\\textasciicircum{}\\textbackslash{}"
+        );
+        assert_eq!(escape("Émile <b>"), "Émile <b>");
+    }
+
+    #[test]
+    fn filters() {
+        assert_eq!(
+            unicode_to_latex("Gödel & Cœur, Çé – “ß”"),
+            "G\\\"odel \\& C\\oe{}ur, \\c{C}\\'e -- ``\\ss{}''"
+        );
+        assert_eq!(slugify("Über die Gödel-Sätze!"), "uber-die-godel-satze");
+        let mut tera = Tera::default();
+        register_filters(&mut tera);
+        assert_eq!(
+            tera.render_str("<~{ 'Привет мир' | slugify }~>", &tera::Context::new())
+                .unwrap(),
+            "privet-mir"
+        );
+        assert_eq!(
+            bibkey("The TeXbook", "Knuth, Donald E.", Some("1984")),
+            "knuth1984texbook"
+        );
+        assert_eq!(
+            bibkey("On Gödel", "Kurt Gödel and A. N. Other", None),
+            "godelgodel"
+        );
+        assert_eq!(initials("Jean-Paul  Sartre"), "J.-P. S.");
+        assert_eq!(
+            title_case("the art of computer programming: a guide to the LaTeX way"),
+            "The Art of Computer Programming: A Guide to the LaTeX Way"
+        );
+        assert_eq!(
+            texpath(".\\figures\\my plot.pdf"),
+            "\"figures/my plot.pdf\""
+        );
+        assert_eq!(
+            [1, 2, 3, 4, 11, 12, 13, 21, 102].map(ordinal),
+            [
+                "1st", "2nd", "3rd", "4th", "11th", "12th", "13th", "21st", "102nd"
+            ]
+        );
+
+        let mut tera = Tera::default();
+        register_filters(&mut tera);
+        let mut context = tera::Context::new();
+        context.insert("n", "22");
+        let rendered = tera
+            .render_str(
+                r#"<~{ n | ordinal }~> <~{ "Émile" | unicode_to_latex }~>"#,
+                &context,
+            )
+            .unwrap();
+        assert_eq!(rendered, "22nd \\'Emile");
+    }
+}
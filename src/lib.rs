@@ -1,8 +1,18 @@
+pub mod builtins;
 pub mod cli;
 pub mod config;
+pub mod conflicts;
+pub mod dates;
+pub mod discovery;
 pub mod errors;
+pub mod expr;
 pub mod filter;
+pub mod history;
 pub mod input;
+pub mod latex;
 pub mod logging;
+pub mod plan;
+pub mod report;
 pub mod templating;
 pub mod tui;
+pub mod variables;
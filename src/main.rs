@@ -4,8 +4,9 @@ use clap::Parser;
 use color_eyre::eyre::eyre;
 use ratatui::crossterm::terminal::disable_raw_mode;
 use templatex::{
-    cli, config, errors,
+    builtins, cli, config, errors,
     filter::Filter,
+    history, input,
     logging::{disable_stdout_logs, enable_stdout_logs, init},
     templating::{self, LoadableDir},
     tui::picker,
@@ -15,6 +16,11 @@ use tracing::{debug, info, level_filters::LevelFilter};
 fn main() -> color_eyre::Result<()> {
     errors::init()?;
     let cli::Cli { name, args } = cli::Cli::parse();
+    let policy = args.conflict_policy();
+    let uses_history = args.uses_history();
+    if let Some(date) = args.date {
+        builtins::set_date(date);
+    }
     let config = if let Some(cdir) = args.config_dir {
         config::Settings::with_source_dir(cdir)?
     } else {
@@ -63,8 +69,13 @@ fn main() -> color_eyre::Result<()> {
             .collect::<Vec<_>>(),
     };
 
+    // The shared partials are included by templates, and aren't a template of their own.
+    let shared_dir = config.get_shared_dir();
+    let template_dirs = templating::template_dirs(template_dirs, shared_dir.as_deref());
+
     info!("Loading templates");
-    let loaded_templates = template_dirs
+    // Ignored templates aren't offered, but other templates can still extend them.
+    let available_templates = template_dirs
         .iter()
         .filter_map(|p| {
             let p = p.load_dir();
@@ -74,18 +85,73 @@ fn main() -> color_eyre::Result<()> {
                 return None;
             };
             debug!("Loaded template dir: {:?}", p);
-
-            if p.config.ignore { None } else { Some(p) }
+            Some(p)
+        })
+        .collect::<Vec<_>>();
+    let loaded_templates = available_templates
+        .iter()
+        .filter(|p| !p.config.ignore)
+        .filter(|p| match p.ancestry(&available_templates) {
+            Ok(_) => true,
+            Err(e) => {
+                tracing::error!("Failed to load template dir {}: {}", p.dir().display(), e);
+                false
+            }
         })
+        .cloned()
         .collect::<Vec<_>>();
 
+    let find_template = |wanted: &str| {
+        loaded_templates
+            .iter()
+            .find(|t| t.is_named(wanted))
+            .cloned()
+    };
+    // Answers are remembered under the name of the template's directory.
+    let history_key = |wanted: &str| {
+        find_template(wanted)
+            .and_then(|t| t.dir().file_name().map(|n| n.display().to_string()))
+            .unwrap_or_else(|| wanted.to_string())
+    };
+    if let Some(wanted) = &args.history {
+        let answers = history::load(&history_key(wanted))?;
+        if answers.is_empty() {
+            println!("No answers remembered for {wanted}");
+        }
+        for (name, value) in &answers {
+            println!("{name} = {value}");
+        }
+        return Ok(());
+    }
+    if let Some(wanted) = &args.clear_history {
+        if history::clear(&history_key(wanted))? {
+            println!("Forgot the answers for {wanted}");
+        } else {
+            println!("No answers remembered for {wanted}");
+        }
+        return Ok(());
+    }
+    let name = name.ok_or_else(|| eyre!("No project name given"))?;
     if loaded_templates.is_empty() {
         tracing::error!("No templates found");
         return Err(eyre!("No templates found"));
     }
     info!("Loaded {} templates", loaded_templates.len());
-    let sel = if loaded_templates.len() == 1 {
+    let sel = if let Some(wanted) = &args.template {
+        find_template(wanted).ok_or_else(|| {
+            let available = loaded_templates
+                .iter()
+                .map(|t| t.name())
+                .collect::<Vec<_>>()
+                .join(", ");
+            eyre!("Template not found: {wanted}. Available templates: {available}")
+        })?
+    } else if loaded_templates.len() == 1 {
         loaded_templates[0].clone()
+    } else if args.no_input {
+        return Err(eyre!(
+            "Multiple templates found. Use --template to pick one when running with --no-input"
+        ));
     } else {
         let theme = config.get_theme();
         disable_stdout_logs()?;
@@ -96,6 +162,7 @@ fn main() -> color_eyre::Result<()> {
 
     disable_raw_mode()?;
     println!("\r\n");
+    let sel = sel.resolved(&available_templates)?;
     let include_filters = sel
         .config
         .include
@@ -112,29 +179,47 @@ fn main() -> color_eyre::Result<()> {
         .include_filters(include_filters)
         .exclude_filters(exclude_filters)
         .template_dirs([sel.dir().clone()])
+        .available_templates(available_templates)
+        .shared_dir(shared_dir)
         .clone()
         .build()?;
 
     let t_name = &sel.dir().file_name().unwrap().display().to_string();
     let template = engine.get_template(t_name).unwrap();
-    let vars = template
-        .files()
-        .iter()
-        .flat_map(|f| f.variables())
-        .collect::<Vec<_>>();
-
-    let data = vars
-        .iter()
-        .map(|v| {
-            let prompt = inquire::prompt_text(format!("Enter value for {}", v)).unwrap();
-            (v.to_string(), prompt)
-        })
-        .collect::<Vec<_>>();
+    for v in template.variables() {
+        debug!(variable = v.name(), usages = ?v.usages(), "Found variable");
+    }
 
+    let provided = input::provided_answers(args.answers.as_deref(), &args.set)?;
     let out_dir = args.out_dir.unwrap_or_else(|| PathBuf::from(name));
+    let builtins = template.builtins(&out_dir);
+    // Remembered answers are more specific than the user's defaults, so they win.
+    let mut defaults = config.get_defaults();
+    if uses_history {
+        match history::load(t_name) {
+            Ok(remembered) => defaults.extend(remembered),
+            Err(e) => tracing::warn!("Ignoring remembered answers: {}", e),
+        }
+    }
+    let data = input::collect_answers(template, &builtins, &provided, &defaults, args.no_input)?;
+
+    if let Some(format) = args.dry_run {
+        let plan = engine.preview(&out_dir, t_name, &data, policy)?;
+        match format {
+            cli::PlanFormat::Tree => print!("{plan}"),
+            cli::PlanFormat::Json => println!("{}", serde_json::to_string_pretty(&plan)?),
+        }
+        return Ok(());
+    }
 
     info!("Rendering template");
-    engine.render(&out_dir, t_name, &data)?;
+    let report = engine.render_with(&out_dir, t_name, &data, policy)?;
+    if !args.silent {
+        print!("{report}");
+    }
+    if let Err(e) = history::save(t_name, &data) {
+        tracing::warn!("Failed to remember the answers: {}", e);
+    }
 
     Ok(())
 }
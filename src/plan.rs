@@ -0,0 +1,245 @@
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// Everything [`Engine::render`](crate::templating::Engine::render) does for a project,
+/// worked out without touching the filesystem.
+#[derive(Serialize, Debug, Clone)]
+pub struct Plan {
+    pub template: String,
+    pub out_dir: PathBuf,
+    pub entries: Vec<PlanEntry>,
+    /// The contents of the generated `Tectonic.toml`.
+    pub tectonic_toml: String,
+    /// Problems that don't stop the project from being generated, such as unused variables.
+    pub warnings: Vec<String>,
+    /// The answers and built-in values every file is rendered with.
+    #[serde(skip)]
+    pub context: tera::Context,
+}
+
+/// What happens to a single file.
+#[derive(Serialize, Debug, Clone)]
+pub struct PlanEntry {
+    /// The file in the template, relative to the template directory. `None` for files that
+    /// templatex generates itself.
+    pub source: Option<PathBuf>,
+    /// Where the file is written, relative to the output directory. `None` if it is skipped.
+    pub target: Option<PathBuf>,
+    #[serde(flatten)]
+    pub action: Action,
+    /// Whether the target already exists and would be replaced.
+    pub overwrite: bool,
+    /// Values added to [`Plan::context`] for this file only, e.g. by a `foreach` rule.
+    #[serde(skip)]
+    pub context: tera::Context,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    /// Rendered with Tera.
+    Render,
+    /// Copied as-is.
+    Copy,
+    /// Rendered or copied, and merged into the existing file with conflict markers.
+    Merge,
+    Skip {
+        #[serde(flatten)]
+        reason: SkipReason,
+    },
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum SkipReason {
+    /// The path matches one of the template's `exclude` filters.
+    Excluded,
+    /// The file is a LaTeX build artifact, such as a `.aux` or `.log` file.
+    BuildArtifact,
+    /// The file is only included or imported by other files.
+    Partial,
+    /// The `when` condition of a `[files]` rule is false.
+    Condition { when: String },
+    /// The target already exists and is kept.
+    Exists,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::Excluded => write!(f, "excluded"),
+            SkipReason::BuildArtifact => write!(f, "build artifact"),
+            SkipReason::Partial => write!(f, "partial"),
+            SkipReason::Condition { when } => write!(f, "`{when}` is false"),
+            SkipReason::Exists => write!(f, "keeps the existing file"),
+        }
+    }
+}
+
+impl PlanEntry {
+    /// An entry that writes `target`, relative to `out_dir`.
+    pub fn write(source: Option<&Path>, target: PathBuf, action: Action, out_dir: &Path) -> Self {
+        Self {
+            source: source.map(Path::to_path_buf),
+            overwrite: out_dir.join(&target).exists(),
+            target: Some(target),
+            action,
+            context: tera::Context::new(),
+        }
+    }
+
+    pub fn is_skipped(&self) -> bool {
+        matches!(self.action, Action::Skip { .. })
+    }
+
+    pub fn skip(source: &Path, reason: SkipReason) -> Self {
+        Self {
+            source: Some(source.to_path_buf()),
+            target: None,
+            action: Action::Skip { reason },
+            overwrite: false,
+            context: tera::Context::new(),
+        }
+    }
+}
+
+/// A directory in a printed tree of files, with the label of each file in it.
+#[derive(Default)]
+pub(crate) struct TreeNode {
+    children: BTreeMap<String, TreeNode>,
+    label: Option<String>,
+}
+
+impl TreeNode {
+    pub(crate) fn insert(&mut self, path: &Path, label: String) {
+        let node = path.components().fold(self, |node, component| {
+            let name = component.as_os_str().display().to_string();
+            node.children.entry(name).or_default()
+        });
+        node.label = Some(label);
+    }
+
+    pub(crate) fn print(&self, f: &mut fmt::Formatter<'_>, indent: &str) -> fmt::Result {
+        let count = self.children.len();
+        for (i, (name, child)) in self.children.iter().enumerate() {
+            let last = i + 1 == count;
+            let branch = if last { "└── " } else { "├── " };
+            match &child.label {
+                Some(label) => writeln!(f, "{indent}{branch}{name} ({label})")?,
+                None => writeln!(f, "{indent}{branch}{name}/")?,
+            }
+            let indent = format!("{indent}{}", if last { "    " } else { "│   " });
+            child.print(f, &indent)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut tree = TreeNode::default();
+        let mut skipped = Vec::new();
+        for entry in &self.entries {
+            let action = match &entry.action {
+                Action::Render => "render",
+                Action::Copy => "copy",
+                Action::Merge => "merge",
+                Action::Skip { reason } => {
+                    if let Some(source) = &entry.source {
+                        skipped.push((source, reason));
+                    }
+                    continue;
+                }
+            };
+            let Some(target) = &entry.target else {
+                continue;
+            };
+            let label = if entry.overwrite && entry.action != Action::Merge {
+                format!("{action}, overwrites existing file")
+            } else {
+                action.to_string()
+            };
+            tree.insert(target, label);
+        }
+        writeln!(f, "{}/", self.out_dir.display())?;
+        tree.print(f, "")?;
+        if !skipped.is_empty() {
+            writeln!(f, "\nSkipped:")?;
+            for (source, reason) in skipped {
+                writeln!(f, "  {} ({})", source.display(), reason)?;
+            }
+        }
+        if !self.warnings.is_empty() {
+            writeln!(f, "\nWarnings:")?;
+            for warning in &self.warnings {
+                writeln!(f, "  {warning}")?;
+            }
+        }
+        writeln!(f, "\nTectonic.toml:")?;
+        for line in self.tectonic_toml.lines() {
+            writeln!(f, "  {line}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test() {
+        let out_dir = Path::new("/nonexistent/report");
+        let plan = Plan {
+            template: "article".into(),
+            out_dir: out_dir.to_path_buf(),
+            entries: vec![
+                PlanEntry::write(
+                    Some(Path::new("main.tex")),
+                    PathBuf::from("src/main.tex"),
+                    Action::Render,
+                    out_dir,
+                ),
+                PlanEntry::write(
+                    Some(Path::new("figures/logo.png")),
+                    PathBuf::from("src/figures/logo.png"),
+                    Action::Copy,
+                    out_dir,
+                ),
+                PlanEntry::skip(Path::new("main.aux"), SkipReason::BuildArtifact),
+                PlanEntry::write(
+                    None,
+                    PathBuf::from("Tectonic.toml"),
+                    Action::Render,
+                    out_dir,
+                ),
+            ],
+            tectonic_toml: "[doc]\nname = \"report\"\n".into(),
+            warnings: Vec::new(),
+            context: tera::Context::new(),
+        };
+        assert_eq!(
+            plan.to_string(),
+            "/nonexistent/report/
+├── Tectonic.toml (render)
+└── src/
+    ├── figures/
+    │   └── logo.png (copy)
+    └── main.tex (render)
+
+Skipped:
+  main.aux (build artifact)
+
+Tectonic.toml:
+  [doc]
+  name = \"report\"
+"
+        );
+        let json = serde_json::to_value(&plan).unwrap();
+        assert_eq!(json["entries"][2]["action"], "skip");
+        assert_eq!(json["entries"][2]["reason"], "build_artifact");
+    }
+}
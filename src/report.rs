@@ -0,0 +1,197 @@
+use crate::plan::{Action, PlanEntry, SkipReason, TreeNode};
+use ratatui::crossterm::style::Stylize;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{fmt, path::PathBuf};
+
+/// What [`Engine::render`](crate::templating::Engine::render) did for a project.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct RenderReport {
+    pub out_dir: PathBuf,
+    pub files: Vec<FileReport>,
+    /// Warnings about the project as a whole, such as variables that are never used.
+    pub warnings: Vec<String>,
+}
+
+/// What happened to a single file.
+#[derive(Serialize, Debug, Clone)]
+pub struct FileReport {
+    /// The file in the template, relative to the template directory. `None` for files that
+    /// templatex generates itself.
+    pub source: Option<PathBuf>,
+    /// The output file, relative to the output directory. `None` if nothing was written.
+    pub path: Option<PathBuf>,
+    pub action: ReportAction,
+    /// The size of the written file.
+    pub bytes: Option<usize>,
+    /// The SHA-256 of the written file, in hex.
+    pub hash: Option<String>,
+    /// Why the file was skipped.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportAction {
+    Rendered,
+    Copied,
+    /// Merged into an existing file with conflict markers.
+    Merged,
+    /// Left out by the template's filters, e.g. build artifacts or `exclude` patterns.
+    SkippedByFilter,
+    /// Left out because a `[files]` condition is false.
+    SkippedByCondition,
+    /// The target already existed and was kept.
+    Conflict,
+}
+
+impl FileReport {
+    /// The report for an entry of the plan that wasn't written.
+    pub fn skipped(entry: &PlanEntry) -> Self {
+        let action = match &entry.action {
+            Action::Skip {
+                reason: SkipReason::Condition { .. },
+            } => ReportAction::SkippedByCondition,
+            Action::Skip {
+                reason: SkipReason::Exists,
+            } => ReportAction::Conflict,
+            _ => ReportAction::SkippedByFilter,
+        };
+        let reason = match &entry.action {
+            Action::Skip { reason } => Some(reason.to_string()),
+            _ => None,
+        };
+        Self {
+            source: entry.source.clone(),
+            path: entry
+                .target
+                .clone()
+                .filter(|_| action == ReportAction::Conflict),
+            action,
+            bytes: None,
+            hash: None,
+            reason,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// The report for an entry of the plan that was written with `contents`.
+    pub fn written(entry: &PlanEntry, contents: &[u8], is_template: bool) -> Self {
+        let action = match entry.action {
+            Action::Merge => ReportAction::Merged,
+            _ if is_template => ReportAction::Rendered,
+            _ => ReportAction::Copied,
+        };
+        let mut warnings = Vec::new();
+        if entry.overwrite && action != ReportAction::Merged {
+            warnings.push("overwrote the existing file".to_string());
+        }
+        Self {
+            source: entry.source.clone(),
+            path: entry.target.clone(),
+            action,
+            bytes: Some(contents.len()),
+            hash: Some(format!("{:x}", Sha256::digest(contents))),
+            reason: None,
+            warnings,
+        }
+    }
+}
+
+/// Formats a file size for people, e.g. `1.5 KiB`.
+fn human_size(bytes: usize) -> String {
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    for unit in ["KiB", "MiB"] {
+        if size < 1024.0 {
+            return format!("{size:.1} {unit}");
+        }
+        size /= 1024.0;
+    }
+    format!("{size:.1} GiB")
+}
+
+impl fmt::Display for RenderReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut tree = TreeNode::default();
+        let mut skipped = Vec::new();
+        for file in &self.files {
+            let action = match file.action {
+                ReportAction::Rendered => "rendered".green(),
+                ReportAction::Copied => "copied".blue(),
+                ReportAction::Merged => "merged".yellow(),
+                ReportAction::Conflict => "kept existing".yellow(),
+                ReportAction::SkippedByFilter | ReportAction::SkippedByCondition => {
+                    skipped.push(file);
+                    continue;
+                }
+            };
+            let Some(path) = &file.path else {
+                continue;
+            };
+            let mut label = action.to_string();
+            if let (Some(bytes), Some(hash)) = (file.bytes, &file.hash) {
+                let details = format!(", {}, {}", human_size(bytes), &hash[..8]);
+                label.push_str(&details.dim().to_string());
+            }
+            for warning in &file.warnings {
+                label.push_str(&format!(", {}", warning.as_str().yellow()));
+            }
+            tree.insert(path, label);
+        }
+        writeln!(f, "{}", format!("{}/", self.out_dir.display()).bold())?;
+        tree.print(f, "")?;
+        if !skipped.is_empty() {
+            writeln!(f, "\n{}", "Skipped:".bold())?;
+            for file in skipped {
+                let source = file.source.clone().unwrap_or_default();
+                let reason = file.reason.as_deref().unwrap_or_default();
+                writeln!(f, "  {} {}", source.display(), format!("({reason})").dim())?;
+            }
+        }
+        if !self.warnings.is_empty() {
+            writeln!(f, "\n{}", "Warnings:".bold().yellow())?;
+            for warning in &self.warnings {
+                writeln!(f, "  {}", warning.as_str().yellow())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    #[test]
+    fn test() {
+        let out_dir = Path::new("/nonexistent/report");
+        let entry = PlanEntry::write(
+            Some(Path::new("main.tex")),
+            PathBuf::from("src/main.tex"),
+            Action::Render,
+            out_dir,
+        );
+        let report = FileReport::written(&entry, b"hello", true);
+        assert_eq!(report.action, ReportAction::Rendered);
+        assert_eq!(report.bytes, Some(5));
+        assert_eq!(
+            report.hash.as_deref(),
+            Some("2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824")
+        );
+        let entry = PlanEntry::skip(
+            Path::new("appendix.tex"),
+            SkipReason::Condition {
+                when: "appendix".into(),
+            },
+        );
+        let report = FileReport::skipped(&entry);
+        assert_eq!(report.action, ReportAction::SkippedByCondition);
+        assert_eq!(report.reason.as_deref(), Some("`appendix` is false"));
+        assert_eq!(human_size(1536), "1.5 KiB");
+    }
+}
@@ -1,18 +1,26 @@
 use crate::{
-    errors::{Error, Result},
+    builtins::{self, Builtins},
+    conflicts::{self, ConflictPolicy},
+    dates, discovery,
+    errors::{Error, RenderError, RenderErrors, Result},
+    expr,
     filter::{Filter, FilterFn},
+    latex,
+    plan::{Action, Plan, PlanEntry, SkipReason},
+    report::{FileReport, RenderReport},
+    variables::{Answers, Variables},
 };
+use color_eyre::eyre::eyre;
 use derive_builder::Builder;
 use getset::{CloneGetters, CopyGetters, Getters, MutGetters, Setters, WithSetters};
 use glob::glob;
+use indexmap::IndexMap;
 use serde::Deserialize;
 use std::{
-    fs,
-    io::{self, Write},
+    fs, io,
     path::{Path, PathBuf},
     sync::LazyLock,
 };
-use tera::ast;
 use tracing::{debug, info, warn};
 
 pub static FILE_FILTER: LazyLock<Filter<&str>> = LazyLock::new(|| {
@@ -37,12 +45,26 @@ pub static FILE_FILTER: LazyLock<Filter<&str>> = LazyLock::new(|| {
     ])
 });
 
-pub static IMAGE_FILTER: LazyLock<Filter<&str>> = LazyLock::new(|| {
-    Filter::<&str>::with_filter(vec![
-        "png", "jpg", "jpeg", "gif", "svg", "bmp", "webp", "ico", "tif", "tiff", "eps", "ps", "ai",
-        "raw", "xcf", "pdf",
-    ])
-});
+/// The prefix of the names under which the files of the templates a template extends are
+/// available, as in `{% extends "@base-article/main.tex" %}`. Files with such names are only
+/// used by other files and never generated.
+pub const PARENT_PREFIX: &str = "@";
+
+/// The file in a template directory that configures the template. It is never generated.
+pub const CONFIG_FILE: &str = "templatex.toml";
+
+/// The directory in a template for files that are only included or imported by other files.
+pub const PARTIALS_DIR: &str = "_templatex";
+
+/// The prefix of the names under which the files of the shared directory are available, as in
+/// `{% include "@shared/logo.tex" %}`.
+pub const SHARED_PREFIX: &str = "@shared/";
+
+/// Whether `contents` is binary, i.e. contains a NUL byte or isn't valid UTF-8. Binary files
+/// are copied as-is instead of being rendered.
+pub fn is_binary(contents: &[u8]) -> bool {
+    contents.contains(&0) || std::str::from_utf8(contents).is_err()
+}
 
 #[derive(Debug, Getters, Setters, WithSetters, MutGetters, CopyGetters, CloneGetters, Builder)]
 #[builder(build_fn(skip))]
@@ -56,6 +78,14 @@ pub struct Engine {
     #[builder(setter(into))]
     #[allow(dead_code)]
     include_filters: Option<Filter<String>>,
+    /// The templates that the templates in `template_dirs` may extend.
+    #[builder(setter(into))]
+    #[allow(dead_code)]
+    available_templates: Vec<LoadedTemplateDir>,
+    /// A directory of partials that every template can include, see [`SHARED_PREFIX`].
+    #[builder(setter(into))]
+    #[allow(dead_code)]
+    shared_dir: Option<PathBuf>,
     #[builder(setter(skip))]
     templates: Vec<Template>,
 }
@@ -66,8 +96,17 @@ pub struct Template {
     tera: tera::Tera,
     name: String,
     dir: PathBuf,
+    /// The directories the template's files come from: its own, then the directory of the
+    /// template it extends, and so on.
+    layers: Vec<PathBuf>,
     files: Vec<TemplateFile>,
-    image_files: Vec<PathBuf>,
+    /// Files that are copied as-is: binary files, see [`is_binary`], and the files matching
+    /// the template's `copy` globs.
+    verbatim_files: Vec<PathBuf>,
+    /// Files in the template directory that are never generated, relative to it.
+    skipped_files: Vec<(PathBuf, SkipReason)>,
+    config: LoadedTemplateDirConfig,
+    variables: Vec<TemplateVariable>,
 }
 
 #[derive(Debug, Getters, Setters, WithSetters, MutGetters, CopyGetters, CloneGetters, Clone)]
@@ -77,6 +116,23 @@ pub struct TemplateFile {
     variables: Vec<String>,
 }
 
+/// A variable needed by a template, along with every place it is used.
+#[derive(Debug, Getters, Setters, WithSetters, MutGetters, CopyGetters, CloneGetters, Clone)]
+#[getset(get_clone = "pub")]
+pub struct TemplateVariable {
+    name: String,
+    usages: Vec<VariableUsage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariableUsage {
+    /// The file that uses the variable, relative to the template directory.
+    pub path: PathBuf,
+    /// The lines the variable appears on. Empty if it is only used through an `include` or in
+    /// the file's name.
+    pub lines: Vec<usize>,
+}
+
 impl EngineBuilder {
     pub fn build(self) -> Result<Engine> {
         let Some(template_dirs) = self.template_dirs else {
@@ -89,62 +145,118 @@ impl EngineBuilder {
         let mut include_filter = self.include_filters.clone().unwrap_or_default();
         let include_filter = include_filter.as_mut();
         let mut templates = Vec::new();
+        let available = self.available_templates.clone().unwrap_or_default();
+        let shared_dir = self.shared_dir.clone().flatten();
+        let shared_files = match &shared_dir {
+            Some(dir) => shared_files(dir)?,
+            None => Vec::new(),
+        };
         for dir in template_dirs.clone() {
+            let name = dir.file_name().unwrap().to_str().unwrap().to_string();
+            let loaded = if dir.is_dir() {
+                dir.load_dir()?
+            } else {
+                LoadedTemplateDir::new(name.clone(), None, dir.clone())
+            };
+            let ancestry = loaded.ancestry(&available)?;
+            let config = loaded.resolved(&available)?.config;
+            let layers = ancestry.iter().map(|t| t.dir.clone()).collect::<Vec<_>>();
             let mut files = Vec::new();
-            let glob = glob(&dir.join("**/*").display().to_string())?.filter_map(|e| e.ok());
-            let mut image_files = Vec::new();
-            let glob = glob.filter(|f| {
-                let Some(ext) = f.extension() else {
-                    return false;
-                };
-                let ext = ext.display().to_string();
-                let ext = ext.as_str();
+            let mut verbatim_files = Vec::new();
+            let mut text_files = Vec::new();
+            let mut skipped_files = Vec::new();
+            // The files of each template replace those at the same path in the templates it
+            // extends. Every parent's text files can also be extended or included as
+            // `@<parent>/<path>`, with the name its child's `extends` gives it.
+            let mut sources = IndexMap::new();
+            for (i, layer) in layers.iter().enumerate().rev() {
+                let glob = glob(&layer.join("**/*").display().to_string())?.filter_map(|e| e.ok());
+                for f in glob {
+                    if f.is_dir() {
+                        continue;
+                    }
+                    let relative = f.strip_prefix(layer).unwrap_or(&f).to_path_buf();
+                    if relative == Path::new(CONFIG_FILE) {
+                        continue;
+                    }
+                    if i > 0
+                        && !is_build_artifact(&f)
+                        && !config.is_copied(&relative)?
+                        && !is_binary(&fs::read(&f)?)
+                    {
+                        let parent = ancestry[i - 1]
+                            .config
+                            .extends
+                            .as_deref()
+                            .unwrap_or_default();
+                        let parent_name = format!("{PARENT_PREFIX}{parent}/{}", relative.display());
+                        text_files.push((f.clone(), Some(parent_name)));
+                    }
+                    sources.insert(relative, f);
+                }
+            }
+            sources.sort_keys();
+            text_files.extend(shared_files.iter().cloned());
+            for (relative, f) in sources {
                 if let Some(exclude) = &exclude_filter
                     && exclude.filter(f.display().to_string())
                 {
-                    return false;
+                    skipped_files.push((relative, SkipReason::Excluded));
+                    continue;
                 }
-                if let Some(include) = &include_filter
-                    && include.filter(f.display().to_string())
-                {
-                    return true;
+                let included = include_filter
+                    .as_ref()
+                    .is_some_and(|include| include.filter(f.display().to_string()));
+                if !included && is_build_artifact(&f) {
+                    skipped_files.push((relative, SkipReason::BuildArtifact));
+                    continue;
                 }
-                if IMAGE_FILTER.filter(ext) {
-                    image_files.push(f.clone());
-                    return false;
+                if is_partial(&relative) {
+                    if !config.is_copied(&relative)? && !is_binary(&fs::read(&f)?) {
+                        text_files.push((f, Some(relative.display().to_string())));
+                    }
+                    skipped_files.push((relative, SkipReason::Partial));
+                    continue;
                 }
-
-                !(FILE_FILTER.filter(ext) || IMAGE_FILTER.filter(ext))
-            });
+                if config.is_copied(&relative)? || is_binary(&fs::read(&f)?) {
+                    verbatim_files.push(f);
+                } else {
+                    text_files.push((f, Some(relative.display().to_string())));
+                }
+            }
             let mut tera = tera::Tera::default();
 
-            tera.add_template_files(glob.filter_map(|f| {
-                let sf = f.strip_prefix(&dir).ok()?;
-                let sf = sf.display().to_string();
-                Some((f, Some(sf)))
-            }))?;
+            tera.add_template_files(text_files)?;
             tera.build_inheritance_chains()?;
-            tera.autoescape_on(vec![".tex"]);
-            for template in tera.get_template_names() {
-                let mut variables = Vec::new();
-                for node in tera.get_template(template).unwrap().ast.clone() {
-                    if let ast::Node::VariableBlock(_, expr) = node
-                        && let ast::ExprVal::Ident(s) = expr.val
-                    {
-                        variables.push(s);
-                    }
-                }
+            latex::register_filters(&mut tera);
+            dates::register(&mut tera);
+            tera.set_escape_fn(latex::escape);
+            // Tera only takes static suffixes. Templates are loaded once per run, so leaking
+            // the few configured extensions is fine.
+            tera.autoescape_on(
+                config
+                    .escaped_extensions()
+                    .into_iter()
+                    .map(|ext| &*Box::leak(ext.into_boxed_str()))
+                    .collect(),
+            );
+            for template in output_names(&tera) {
                 files.push(TemplateFile {
-                    variables,
-                    path: dir.join(template),
+                    variables: discovery::template_variables(&tera, template),
+                    path: source_path(&layers, Path::new(template)),
                 });
             }
+            let variables = Template::variable_model(&config, &layers, &files, &verbatim_files)?;
             templates.push(Template {
                 tera,
-                name: dir.file_name().unwrap().to_str().unwrap().to_string(),
+                name,
                 dir,
+                layers,
                 files,
-                image_files,
+                verbatim_files,
+                skipped_files,
+                config,
+                variables,
             });
         }
         Ok(Engine {
@@ -152,10 +264,217 @@ impl EngineBuilder {
             templates,
             exclude_filters: None,
             include_filters: None,
+            available_templates: available,
+            shared_dir,
         })
     }
 }
 
+impl Template {
+    /// Merges the variables of every file into one list: declared variables first, in the
+    /// order of `templatex.toml`, then the rest in order of first appearance. Variables used
+    /// in file names come after those used in file contents.
+    fn variable_model(
+        config: &LoadedTemplateDirConfig,
+        layers: &[PathBuf],
+        files: &[TemplateFile],
+        verbatim_files: &[PathBuf],
+    ) -> Result<Vec<TemplateVariable>> {
+        let mut variables = config
+            .variables
+            .keys()
+            .filter(|name| *name != builtins::NAMESPACE)
+            .map(|name| TemplateVariable {
+                name: name.clone(),
+                usages: Vec::new(),
+            })
+            .collect::<Vec<_>>();
+        for file in files {
+            let source = fs::read_to_string(&file.path).unwrap_or_default();
+            let path = relative_path(layers, &file.path);
+            let rule = config.foreach_rule(path)?;
+            let loop_names = rule.map(FileRule::loop_names).unwrap_or_default();
+            for name in &file.variables {
+                if loop_names.contains(&name.as_str()) {
+                    continue;
+                }
+                let usage = VariableUsage {
+                    path: path.to_path_buf(),
+                    lines: discovery::usage_lines(&source, name),
+                };
+                Self::add_usage(&mut variables, name, usage);
+            }
+        }
+        let paths = files.iter().map(|f| &f.path).chain(verbatim_files);
+        for path in paths {
+            let path = relative_path(layers, path);
+            for name in config.path_variables(path)? {
+                let usage = VariableUsage {
+                    path: path.to_path_buf(),
+                    lines: Vec::new(),
+                };
+                Self::add_usage(&mut variables, &name, usage);
+            }
+        }
+        Ok(variables)
+    }
+
+    fn add_usage(variables: &mut Vec<TemplateVariable>, name: &str, usage: VariableUsage) {
+        if name == builtins::NAMESPACE {
+            return;
+        }
+        match variables.iter_mut().find(|v| v.name == name) {
+            Some(variable) if !variable.usages.iter().any(|u| u.path == usage.path) => {
+                variable.usages.push(usage)
+            }
+            Some(_) => {}
+            None => variables.push(TemplateVariable {
+                name: name.to_string(),
+                usages: vec![usage],
+            }),
+        }
+    }
+
+    /// The `when` condition that keeps the file at `path`, relative to the template directory,
+    /// from being generated. A file is skipped if a `[files]` rule matching it or one of its
+    /// parent directories has a `when` condition that is false for the answers in `context`.
+    fn false_condition(
+        &self,
+        path: &Path,
+        tera: &mut tera::Tera,
+        context: &tera::Context,
+    ) -> Result<Option<&str>> {
+        for (pattern, rule) in &self.config.files {
+            let Some(when) = &rule.when else {
+                continue;
+            };
+            if matches_path(pattern, path)? && !expr::is_true(tera, when, context)? {
+                debug!("Skipping {}: `{}` is false", path.display(), when);
+                return Ok(Some(when));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Where the file at `path`, relative to the template directory, is written to, relative
+    /// to the project's `src` directory. Each component is first looked up in `[rename]`, and
+    /// then rendered against `context` if it contains `<~{ }~>` tags. Fails if a name renders
+    /// to something that isn't a single file name.
+    fn output_path(
+        &self,
+        path: &Path,
+        tera: &mut tera::Tera,
+        context: &tera::Context,
+    ) -> Result<PathBuf> {
+        let mut output = PathBuf::new();
+        for (source, name) in self.config.component_names(path) {
+            if !expr::is_template(&name) {
+                output.push(name);
+                continue;
+            }
+            let rendered = expr::render(tera, &name, context)?;
+            let rendered = rendered.trim();
+            if rendered.is_empty()
+                || rendered == "."
+                || rendered == ".."
+                || rendered.contains(['/', '\\'])
+            {
+                return Err(file_name_error(&name, &source, rendered));
+            }
+            output.push(rendered);
+        }
+        Ok(output)
+    }
+
+    /// The paths the file at `path` is written to, relative to the project's `src` directory,
+    /// each with the values to add to the context when rendering it. A file matching a
+    /// `foreach` rule is generated once per element of the list, with the element, its
+    /// `index` and its zero-padded `number` in the context. Other files are generated once.
+    fn outputs(
+        &self,
+        path: &Path,
+        tera: &mut tera::Tera,
+        context: &tera::Context,
+    ) -> Result<Vec<(PathBuf, tera::Context)>> {
+        let output = self.output_path(path, tera, context)?;
+        let Some(rule) = self.config.foreach_rule(path)? else {
+            return Ok(vec![(output, tera::Context::new())]);
+        };
+        let foreach = rule.foreach.as_deref().unwrap_or_default();
+        let tera::Value::Array(items) = expr::evaluate(tera, foreach, context)? else {
+            return Err(Error::Other(eyre!(
+                "`foreach = \"{}\"` for {} is not a list",
+                foreach,
+                path.display()
+            )));
+        };
+        let name = match &rule.name {
+            Some(name) => name.clone(),
+            None => {
+                let stem = output.file_stem().unwrap_or_default().display();
+                match output.extension() {
+                    Some(ext) => format!("{stem}-<~{{ number }}~>.{}", ext.display()),
+                    None => format!("{stem}-<~{{ number }}~>"),
+                }
+            }
+        };
+        let parent = output.parent().map(Path::to_path_buf).unwrap_or_default();
+        let width = items.len().to_string().len().max(2);
+        let mut outputs = Vec::new();
+        for (i, item) in items.into_iter().enumerate() {
+            let mut item_context = tera::Context::new();
+            item_context.insert(rule.item_name(), &item);
+            item_context.insert("index", &(i + 1));
+            item_context.insert("number", &format!("{:0width$}", i + 1));
+            let mut name_context = context.clone();
+            name_context.extend(item_context.clone());
+            let rendered = expr::render(tera, &name, &name_context)?;
+            let rendered = rendered.trim();
+            let is_valid = rendered
+                .split('/')
+                .all(|c| !(c.is_empty() || c == "." || c == ".." || c.contains('\\')));
+            if !is_valid {
+                return Err(file_name_error(&name, path, rendered));
+            }
+            outputs.push((parent.join(rendered), item_context));
+        }
+        Ok(outputs)
+    }
+
+    /// The built-in `templatex.*` values for a project generated into `out_dir`.
+    pub fn builtins(&self, out_dir: &Path) -> Builtins {
+        Builtins::new(out_dir, &self.name, &self.dir)
+    }
+
+    /// The names of all variables in [`Template::variables`], in prompt order.
+    pub fn variable_names(&self) -> Vec<String> {
+        self.variables.iter().map(|v| v.name.clone()).collect()
+    }
+
+    /// The answers in `data` that no file, file name, `[files]` rule or other variable reads.
+    pub fn unused_variables(&self, data: &Answers) -> Result<Vec<String>> {
+        let mut used = self
+            .variables
+            .iter()
+            .filter(|v| !v.usages.is_empty())
+            .map(|v| v.name.clone())
+            .collect::<Vec<_>>();
+        for config in self.config.variables.values() {
+            used.extend(config.dependencies()?);
+        }
+        for rule in self.config.files.values() {
+            for expr in [&rule.when, &rule.foreach].into_iter().flatten() {
+                used.extend(expr::dependencies(expr)?);
+            }
+        }
+        Ok(data
+            .keys()
+            .filter(|name| *name != builtins::NAMESPACE && !used.contains(name))
+            .cloned()
+            .collect())
+    }
+}
+
 impl Engine {
     pub fn from_values(template_dirs: Vec<PathBuf>, templates: Vec<Template>) -> Self {
         Self {
@@ -163,86 +482,363 @@ impl Engine {
             templates,
             exclude_filters: None,
             include_filters: None,
+            available_templates: Vec::new(),
+            shared_dir: None,
         }
     }
     pub fn get_template(&self, name: &str) -> Option<&Template> {
         self.templates.iter().find(|t| t.name == name)
     }
-    pub fn render(&self, out_dir: &Path, name: &str, data: &[(String, String)]) -> Result<()> {
-        let template = self
-            .templates
-            .iter()
-            .find(|t| t.name == name)
-            .ok_or(Error::Other(color_eyre::eyre::eyre!(
-                "Template not found: {}",
-                name
-            )))?;
+    /// Generates the project for template `name` into `out_dir`, following [`Engine::plan`].
+    pub fn render(&self, out_dir: &Path, name: &str, data: &Answers) -> Result<RenderReport> {
+        self.render_with(out_dir, name, data, ConflictPolicy::default())
+    }
+
+    /// Works out which files rendering template `name` into `out_dir` renders, copies, skips
+    /// or overwrites, without writing anything.
+    pub fn plan(&self, out_dir: &Path, name: &str, data: &Answers) -> Result<Plan> {
+        let template = self.find_template(name)?;
+        let mut builtins = template.builtins(out_dir);
         let mut context = tera::Context::new();
         for (k, v) in data {
             context.insert(k, &v);
         }
-        let template_source_dir = template.dir();
-        for f in template.image_files() {
-            let output_file = out_dir
-                .join("src")
-                .join(f.strip_prefix(&template_source_dir).unwrap());
-            debug!("Rendering {}", output_file.display());
-            let Some(prefix) = output_file.parent() else {
-                warn!("Failed to get parent of {}", output_file.display());
-                continue;
-            };
-
-            std::fs::create_dir_all(prefix)?;
-            std::fs::create_dir_all(out_dir.join("src"))?;
-            debug!(prefix = ?prefix.display(), out_dir = ?out_dir.display(),src_dir = ?out_dir.join("src").display(), "Creating directory");
-            //std::fs::File::create(&output_file)?;
-            std::fs::copy(f, output_file)?;
-        }
-        for f in template.tera.get_template_names() {
-            let output_file = out_dir.join("src").join(f);
-            info!("Rendering {}", output_file.display());
-            let Some(prefix) = output_file.parent() else {
-                warn!("Failed to get parent of {}", output_file.display());
-                continue;
-            };
-
-            std::fs::create_dir_all(prefix)?;
-            std::fs::create_dir_all(out_dir.join("src"))?;
-            if FILE_FILTER.filter(f) {
+        builtins.insert_into(&mut context);
+        let mut tera = template.tera.clone();
+        let mut entries = Vec::new();
+        // Work out every output path first, so that all templates can list the files that
+        // are generated once per list element.
+        let mut rendered_files = Vec::new();
+        for f in output_names(&template.tera) {
+            let path = Path::new(f);
+            if let Some(when) = template.false_condition(path, &mut tera, &context)? {
+                let when = when.to_string();
+                entries.push(PlanEntry::skip(path, SkipReason::Condition { when }));
                 continue;
             }
-            debug!(prefix = ?prefix.display(), out_dir = ?out_dir.display(),src_dir = ?out_dir.join("src").display(), "Creating directory");
-            let mut file = std::fs::File::create(out_dir.join("src").join(f))?;
-            info!("Rendering {}", f);
-            let render_result = template.tera.render(f, &context);
-            debug!(render_result = ?render_result, "Rendered");
-            let Ok(rendered) = render_result else {
-                tracing::error!(
-                    "Failed to render template {}: {}",
-                    f,
-                    render_result.err().unwrap()
-                );
+            let outputs = template.outputs(path, &mut tera, &context)?;
+            if template.config.foreach_rule(path)?.is_some() {
+                let paths = outputs.iter().map(|(p, _)| p.display().to_string());
+                builtins.files.insert(f.to_string(), paths.collect());
+            }
+            rendered_files.push((path, outputs));
+        }
+        builtins.insert_into(&mut context);
+        for f in &template.verbatim_files {
+            let relative = relative_path(&template.layers, f);
+            if let Some(when) = template.false_condition(relative, &mut tera, &context)? {
+                let when = when.to_string();
+                entries.push(PlanEntry::skip(relative, SkipReason::Condition { when }));
                 continue;
-            };
-            file.write_all(rendered.as_bytes())?;
+            }
+            for (output, _) in template.outputs(relative, &mut tera, &context)? {
+                let target = Path::new("src").join(output);
+                entries.push(PlanEntry::write(
+                    Some(relative),
+                    target,
+                    Action::Copy,
+                    out_dir,
+                ));
+            }
+        }
+        for (path, outputs) in rendered_files {
+            for (output, item_context) in outputs {
+                let target = Path::new("src").join(output);
+                let mut entry = PlanEntry::write(Some(path), target, Action::Render, out_dir);
+                entry.context = item_context;
+                entries.push(entry);
+            }
+        }
+        for (path, reason) in &template.skipped_files {
+            entries.push(PlanEntry::skip(path, reason.clone()));
         }
-        let project_name = out_dir
-            .file_name()
-            .unwrap_or_default()
-            .display()
-            .to_string();
         let toml_str = include_str!("../tectonic_files/sample.toml");
         let mut temp_ctx = tera::Context::new();
-        temp_ctx.insert("name", &project_name);
-        let mut tera = template.tera.clone();
-        let toml = tera.render_str(toml_str, &temp_ctx)?;
+        temp_ctx.insert("name", &builtins.name);
+        builtins.insert_into(&mut temp_ctx);
+        let tectonic_toml = tera.render_str(toml_str, &temp_ctx)?;
+        entries.push(PlanEntry::write(
+            None,
+            PathBuf::from("Tectonic.toml"),
+            Action::Render,
+            out_dir,
+        ));
+        let warnings = template
+            .unused_variables(data)?
+            .into_iter()
+            .map(|name| format!("Variable `{name}` is never used"))
+            .collect();
 
-        fs::write(out_dir.join("Tectonic.toml"), toml)?;
+        Ok(Plan {
+            template: name.to_string(),
+            out_dir: out_dir.to_path_buf(),
+            entries,
+            tectonic_toml,
+            warnings,
+            context,
+        })
+    }
+
+    /// [`Engine::plan`], with the files that already exist handled according to `policy` the
+    /// way [`Engine::render_with`] handles them. With [`ConflictPolicy::Ask`] they are still
+    /// marked as overwritten, since a preview doesn't ask.
+    pub fn preview(
+        &self,
+        out_dir: &Path,
+        name: &str,
+        data: &Answers,
+        policy: ConflictPolicy,
+    ) -> Result<Plan> {
+        let mut plan = self.plan(out_dir, name, data)?;
+        if matches!(policy, ConflictPolicy::Refuse | ConflictPolicy::Skip) {
+            conflicts::resolve(self, &mut plan, policy)?;
+        }
+        Ok(plan)
+    }
+
+    /// Generates the project for template `name` into `out_dir`, following [`Engine::plan`].
+    /// Files that already exist are handled according to `policy`.
+    pub fn render_with(
+        &self,
+        out_dir: &Path,
+        name: &str,
+        data: &Answers,
+        policy: ConflictPolicy,
+    ) -> Result<RenderReport> {
+        let mut plan = self.plan(out_dir, name, data)?;
+        conflicts::resolve(self, &mut plan, policy)?;
+        self.execute(&plan)
+    }
+
+    /// Writes the files of `plan`. Everything is written to a staging directory next to the
+    /// output directory first, and only moved into place once every file has succeeded, so a
+    /// failed run leaves nothing behind. Fails with every file that couldn't be generated.
+    pub fn execute(&self, plan: &Plan) -> Result<RenderReport> {
+        let out_dir = &plan.out_dir;
+        let staging = staging_dir(out_dir)?;
+        debug!(staging = ?staging.path().display(), "Staging project");
+        let mut report = RenderReport {
+            out_dir: out_dir.clone(),
+            files: Vec::new(),
+            warnings: plan.warnings.clone(),
+        };
+        let mut written = Vec::new();
+        let mut failures = Vec::new();
+        for entry in &plan.entries {
+            let Some(target) = entry.target.as_ref().filter(|_| !entry.is_skipped()) else {
+                report.files.push(FileReport::skipped(entry));
+                continue;
+            };
+            info!("Writing {}", out_dir.join(target).display());
+            match self.stage(plan, entry, &staging.path().join(target)) {
+                Ok(contents) => {
+                    let is_template = self.is_template(plan, entry)?;
+                    report
+                        .files
+                        .push(FileReport::written(entry, &contents, is_template));
+                    written.push(target);
+                }
+                Err(e) => {
+                    tracing::error!("Failed to generate {}: {}", target.display(), e);
+                    failures.push(RenderError::new(target, &e));
+                }
+            }
+        }
+        if !failures.is_empty() {
+            // Dropping `staging` removes it along with everything written so far.
+            return Err(RenderErrors(failures).into());
+        }
+        if !out_dir.exists() {
+            fs::rename(staging.keep(), out_dir)?;
+            return Ok(report);
+        }
+        for target in written {
+            let output_file = out_dir.join(target);
+            if let Some(prefix) = output_file.parent() {
+                fs::create_dir_all(prefix)?;
+            }
+            fs::rename(staging.path().join(target), output_file)?;
+        }
 
-        Ok(())
+        Ok(report)
+    }
+
+    /// Writes the contents of `entry` to `staged`, and returns them.
+    fn stage(&self, plan: &Plan, entry: &PlanEntry, staged: &Path) -> Result<Vec<u8>> {
+        if let Some(prefix) = staged.parent() {
+            fs::create_dir_all(prefix)?;
+        }
+        let contents = self.contents(plan, entry)?;
+        if entry.action == Action::Merge {
+            let target = entry.target.as_deref().unwrap_or(Path::new(""));
+            let existing = fs::read_to_string(plan.out_dir.join(target))?;
+            let merged = conflicts::merge(&existing, &String::from_utf8_lossy(&contents));
+            fs::write(staged, &merged)?;
+            return Ok(merged.into_bytes());
+        }
+        if entry.action == Action::Copy
+            && let Some(source) = &entry.source
+        {
+            // `fs::copy` keeps the permissions, e.g. of an executable `build.sh`.
+            let template = self.find_template(&plan.template)?;
+            fs::copy(source_path(&template.layers, source), staged)?;
+            return Ok(contents);
+        }
+        fs::write(staged, &contents)?;
+        Ok(contents)
+    }
+
+    /// Whether `entry` of `plan` is rendered with Tera rather than copied.
+    fn is_template(&self, plan: &Plan, entry: &PlanEntry) -> Result<bool> {
+        let template = self.find_template(&plan.template)?;
+        Ok(match &entry.source {
+            Some(source) => template
+                .tera
+                .get_template_names()
+                .any(|n| Path::new(n) == source),
+            None => true,
+        })
+    }
+
+    /// The contents `entry` of `plan` writes: the rendered template, the copied file, or the
+    /// generated `Tectonic.toml`.
+    pub fn contents(&self, plan: &Plan, entry: &PlanEntry) -> Result<Vec<u8>> {
+        let template = self.find_template(&plan.template)?;
+        let Some(source) = &entry.source else {
+            return Ok(plan.tectonic_toml.clone().into_bytes());
+        };
+        if !self.is_template(plan, entry)? {
+            return Ok(fs::read(source_path(&template.layers, source))?);
+        }
+        let mut context = plan.context.clone();
+        context.extend(entry.context.clone());
+        let rendered = template
+            .tera
+            .render(&source.display().to_string(), &context)?;
+        debug!(rendered, "Rendered");
+        Ok(rendered.into_bytes())
+    }
+
+    fn find_template(&self, name: &str) -> Result<&Template> {
+        self.templates
+            .iter()
+            .find(|t| t.name == name)
+            .ok_or(Error::Other(eyre!("Template not found: {}", name)))
     }
 }
 
+/// Creates an empty directory next to `out_dir` to generate the project in.
+fn staging_dir(out_dir: &Path) -> Result<tempfile::TempDir> {
+    let out_dir = std::path::absolute(out_dir)?;
+    let parent = out_dir.parent().unwrap_or(&out_dir);
+    fs::create_dir_all(parent)?;
+    let name = out_dir
+        .file_name()
+        .unwrap_or_default()
+        .display()
+        .to_string();
+    Ok(tempfile::Builder::new()
+        .prefix(&format!(".{name}.templatex-"))
+        .tempdir_in(parent)?)
+}
+
+fn file_name_error(name: &str, source: &Path, rendered: &str) -> Error {
+    Error::Other(eyre!(
+        "The name `{}` of {} renders to `{}`, which is not a valid file name",
+        name,
+        source.display(),
+        rendered
+    ))
+}
+
+/// The directories in `dirs` that are templates: all of them except the shared directory,
+/// which only holds partials for other templates.
+pub fn template_dirs(dirs: Vec<PathBuf>, shared_dir: Option<&Path>) -> Vec<PathBuf> {
+    let shared = shared_dir.and_then(|dir| dir.canonicalize().ok());
+    dirs.into_iter()
+        .filter(|dir| shared.is_none() || dir.canonicalize().ok() != shared)
+        .collect()
+}
+
+/// The text files in the shared directory `dir`, along with the names they are available as.
+fn shared_files(dir: &Path) -> Result<Vec<(PathBuf, Option<String>)>> {
+    let mut files = Vec::new();
+    for f in glob(&dir.join("**/*").display().to_string())?.filter_map(|e| e.ok()) {
+        if f.is_dir() || is_build_artifact(&f) || is_binary(&fs::read(&f)?) {
+            continue;
+        }
+        let relative = f.strip_prefix(dir).unwrap_or(&f).display().to_string();
+        files.push((f, Some(format!("{SHARED_PREFIX}{relative}"))));
+    }
+    Ok(files)
+}
+
+/// Whether the file at `path`, relative to its template directory, is a partial: a file that
+/// other files can include or import, but that is never generated itself. Partials either
+/// start with `_` or are in the [`PARTIALS_DIR`] directory.
+pub fn is_partial(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('_'))
+        || path.components().any(|c| c.as_os_str() == PARTIALS_DIR)
+}
+
+/// Whether the Tera template called `name` is generated, rather than only used by other files.
+fn is_output(name: &str) -> bool {
+    !name.starts_with(PARENT_PREFIX) && !is_partial(Path::new(name))
+}
+
+/// The names of the templates in `tera` that are generated, in a stable order.
+fn output_names(tera: &tera::Tera) -> Vec<&str> {
+    // Tera keeps its templates in a hash map, so sort them to get a stable order.
+    let mut names = tera
+        .get_template_names()
+        .filter(|name| is_output(name))
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+/// Whether `path` is a LaTeX build artifact, judging by its extension. PDFs only count as one
+/// next to a `.tex` file with the same stem, so that PDF figures are kept.
+fn is_build_artifact(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let Some(ext) = FILE_FILTER
+        .get_filter()
+        .iter()
+        .find(|ext| name.ends_with(&format!(".{ext}")))
+    else {
+        return false;
+    };
+    *ext != "pdf" || path.with_extension("tex").exists()
+}
+
+/// `path` relative to the layer of a template it is in.
+fn relative_path<'a>(layers: &[PathBuf], path: &'a Path) -> &'a Path {
+    layers
+        .iter()
+        .find_map(|layer| path.strip_prefix(layer).ok())
+        .unwrap_or(path)
+}
+
+/// The file that `relative` comes from: the one in the first layer of a template that has it.
+fn source_path(layers: &[PathBuf], relative: &Path) -> PathBuf {
+    layers
+        .iter()
+        .map(|layer| layer.join(relative))
+        .find(|path| path.exists())
+        .unwrap_or_else(|| layers[0].join(relative))
+}
+
+/// Returns true if the glob `pattern` matches `path` or one of its parent directories, so
+/// that a pattern naming a directory applies to everything inside it.
+pub fn matches_path(pattern: &str, path: &Path) -> Result<bool> {
+    let pattern = glob::Pattern::new(pattern.trim_end_matches('/'))?;
+    Ok(path
+        .ancestors()
+        .filter(|p| !p.as_os_str().is_empty())
+        .any(|p| pattern.matches_path(p)))
+}
+
 #[derive(Debug, Clone)]
 pub struct LoadedTemplateDir {
     pub config: LoadedTemplateDirConfig,
@@ -256,8 +852,165 @@ pub struct LoadedTemplateDirConfig {
     pub description: Option<String>,
     #[serde(default)]
     pub ignore: bool,
+    /// The name of the template this one is based on. Its files, variables and rules are
+    /// used unless this template replaces them.
+    pub extends: Option<String>,
     pub exclude: Option<Vec<String>>,
     pub include: Option<Vec<String>>,
+    /// Extensions of the files whose values are LaTeX-escaped. Defaults to
+    /// [`latex::ESCAPED_EXTENSIONS`].
+    pub escape: Option<Vec<String>>,
+    #[serde(default)]
+    pub variables: Variables,
+    /// Rules for the files matching each glob, relative to the template directory.
+    #[serde(default)]
+    pub files: IndexMap<String, FileRule>,
+    /// New names for files and directories, by their path relative to the template directory.
+    /// The names may contain `<~{ }~>` tags.
+    #[serde(default)]
+    pub rename: IndexMap<String, String>,
+    /// Globs, relative to the template directory, of files that are copied byte-for-byte
+    /// instead of being rendered, e.g. vendored `.sty` files.
+    #[serde(default)]
+    pub copy: Vec<String>,
+}
+
+impl LoadedTemplateDirConfig {
+    /// Merges the configuration of the template this one extends into it. Variables, `[files]`
+    /// rules and `[rename]` entries of this template replace those of `parent` with the same
+    /// key, and filters and `copy` globs are combined.
+    pub fn inherit(mut self, parent: &Self) -> Self {
+        fn merged<V: Clone>(
+            parent: &IndexMap<String, V>,
+            child: IndexMap<String, V>,
+        ) -> IndexMap<String, V> {
+            let mut merged = parent.clone();
+            merged.extend(child);
+            merged
+        }
+        fn combined(
+            parent: &Option<Vec<String>>,
+            child: Option<Vec<String>>,
+        ) -> Option<Vec<String>> {
+            match (parent, child) {
+                (Some(parent), Some(child)) => Some([parent.clone(), child].concat()),
+                (parent, child) => child.or_else(|| parent.clone()),
+            }
+        }
+        self.variables = merged(&parent.variables, self.variables);
+        self.files = merged(&parent.files, self.files);
+        self.rename = merged(&parent.rename, self.rename);
+        self.exclude = combined(&parent.exclude, self.exclude);
+        self.include = combined(&parent.include, self.include);
+        self.escape = self.escape.or_else(|| parent.escape.clone());
+        self.copy = [parent.copy.clone(), self.copy].concat();
+        self
+    }
+
+    /// The `[rename]` entry for the file or directory at `path`, if there is one.
+    pub fn rename_of(&self, path: &Path) -> Option<&str> {
+        self.rename
+            .iter()
+            .find(|(source, _)| Path::new(source.trim_end_matches('/')) == path)
+            .map(|(_, name)| name.as_str())
+    }
+
+    /// The variables read by the name of the file at `path` and the names of its parent
+    /// directories, including names given in `[rename]`, and by its `foreach` rule.
+    pub fn path_variables(&self, path: &Path) -> Result<Vec<String>> {
+        let mut variables = Vec::new();
+        for (_, name) in self.component_names(path) {
+            if expr::is_template(&name) {
+                variables.extend(expr::template_dependencies(&name)?);
+            }
+        }
+        if let Some(rule) = self.foreach_rule(path)? {
+            let foreach = rule.foreach.as_deref().unwrap_or_default();
+            variables.extend(expr::dependencies(foreach)?);
+            if let Some(name) = &rule.name {
+                let loop_names = rule.loop_names();
+                let deps = expr::template_dependencies(name)?;
+                variables.extend(
+                    deps.into_iter()
+                        .filter(|d| !loop_names.contains(&d.as_str())),
+                );
+            }
+        }
+        Ok(variables)
+    }
+
+    /// The extensions, with a leading dot, of the files whose values are LaTeX-escaped.
+    pub fn escaped_extensions(&self) -> Vec<String> {
+        match &self.escape {
+            Some(extensions) => extensions
+                .iter()
+                .map(|ext| format!(".{}", ext.trim_start_matches('.')))
+                .collect(),
+            None => latex::ESCAPED_EXTENSIONS.map(String::from).to_vec(),
+        }
+    }
+
+    /// Whether the file at `path` matches one of the `copy` globs.
+    pub fn is_copied(&self, path: &Path) -> Result<bool> {
+        for pattern in &self.copy {
+            if matches_path(pattern, path)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// The first `[files]` rule with a `foreach` that matches `path`.
+    pub fn foreach_rule(&self, path: &Path) -> Result<Option<&FileRule>> {
+        for (pattern, rule) in &self.files {
+            if rule.foreach.is_some() && matches_path(pattern, path)? {
+                return Ok(Some(rule));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Pairs the path of each component of `path` with its name after `[rename]`.
+    fn component_names(&self, path: &Path) -> Vec<(PathBuf, String)> {
+        let mut source = PathBuf::new();
+        let mut names = Vec::new();
+        for component in path.components() {
+            source.push(component);
+            let name = match self.rename_of(&source) {
+                Some(name) => name.to_string(),
+                None => component.as_os_str().display().to_string(),
+            };
+            names.push((source.clone(), name));
+        }
+        names
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct FileRule {
+    /// A Tera expression over the answers. Matching files are only generated if it is true.
+    pub when: Option<String>,
+    /// A Tera expression, usually the name of a list variable. Matching files are generated
+    /// once per element of the list.
+    pub foreach: Option<String>,
+    /// The name the element is available as while rendering a `foreach` file. Defaults to
+    /// `item`.
+    #[serde(rename = "as")]
+    pub item: Option<String>,
+    /// The output path of each `foreach` file, relative to the directory the file is in. May
+    /// read the element, `index` and `number`. Defaults to `<stem>-<number>.<ext>`.
+    pub name: Option<String>,
+}
+
+impl FileRule {
+    pub fn item_name(&self) -> &str {
+        self.item.as_deref().unwrap_or("item")
+    }
+
+    /// The names a `foreach` rule adds to the context of each generated file.
+    pub fn loop_names(&self) -> [&str; 3] {
+        [self.item_name(), "index", "number"]
+    }
 }
 
 impl LoadedTemplateDir {
@@ -266,8 +1019,14 @@ impl LoadedTemplateDir {
             name,
             description,
             ignore: false,
+            extends: None,
             exclude: None,
             include: None,
+            escape: None,
+            variables: Variables::new(),
+            files: IndexMap::new(),
+            rename: IndexMap::new(),
+            copy: Vec::new(),
         };
         Self { config, dir }
     }
@@ -283,6 +1042,69 @@ impl LoadedTemplateDir {
     pub fn dir(&self) -> &PathBuf {
         &self.dir
     }
+
+    /// The template's name, or the name of its directory if it has none.
+    fn display_name(&self) -> String {
+        match self.name() {
+            "" => self
+                .dir
+                .file_name()
+                .unwrap_or_default()
+                .display()
+                .to_string(),
+            name => name.to_string(),
+        }
+    }
+
+    /// Whether `name` refers to this template, by its name or the name of its directory.
+    pub fn is_named(&self, name: &str) -> bool {
+        self.name() == name || self.dir.ends_with(name)
+    }
+
+    /// This template, followed by the template it extends among `available`, and so on. Fails
+    /// if a template extends one that doesn't exist, or if templates extend each other in a
+    /// cycle.
+    pub fn ancestry(&self, available: &[LoadedTemplateDir]) -> Result<Vec<LoadedTemplateDir>> {
+        let mut ancestry = vec![self.clone()];
+        let mut current = self;
+        while let Some(parent_name) = &current.config.extends {
+            let Some(parent) = available.iter().find(|t| t.is_named(parent_name)) else {
+                return Err(Error::Other(eyre!(
+                    "Template `{}` extends `{}`, which doesn't exist",
+                    current.display_name(),
+                    parent_name
+                )));
+            };
+            if ancestry.iter().any(|t| t.dir == parent.dir) {
+                let cycle = ancestry
+                    .iter()
+                    .chain([parent])
+                    .map(|t| format!("`{}`", t.display_name()))
+                    .collect::<Vec<_>>()
+                    .join(" -> ");
+                return Err(Error::Other(eyre!(
+                    "Templates extend each other in a cycle: {}",
+                    cycle
+                )));
+            }
+            ancestry.push(parent.clone());
+            current = parent;
+        }
+        Ok(ancestry)
+    }
+
+    /// This template with the configuration of every template it extends merged in, see
+    /// [`LoadedTemplateDirConfig::inherit`].
+    pub fn resolved(&self, available: &[LoadedTemplateDir]) -> Result<LoadedTemplateDir> {
+        let config = self
+            .ancestry(available)?
+            .into_iter()
+            .rev()
+            .map(|t| t.config)
+            .reduce(|parent, child| child.inherit(&parent))
+            .unwrap_or_else(|| self.config.clone());
+        Ok(LoadedTemplateDir::from_config(config, self.dir.clone()))
+    }
 }
 
 pub trait LoadableDir {
@@ -294,7 +1116,7 @@ impl LoadableDir for PathBuf {
         if !self.is_dir() {
             return Err(Error::IoError(io::ErrorKind::NotADirectory.into()));
         }
-        let conf = self.join("templatex.toml");
+        let conf = self.join(CONFIG_FILE);
         debug!("{}", conf.display());
         debug!(exists = ?conf.exists(), "File exists");
         // if !conf.try_exists()? {
@@ -328,6 +1150,26 @@ impl LoadableDir for PathBuf {
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    /// A temporary directory with a template `article` made of `files`.
+    fn template_dir(files: &[(&str, &str)]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (path, contents) in files {
+            let path = dir.path().join("article").join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        dir
+    }
+
+    fn engine(dir: &Path) -> Engine {
+        EngineBuilder::default()
+            .template_dirs(vec![dir.join("article")])
+            .clone()
+            .build()
+            .unwrap()
+    }
+
     #[test]
     fn test() {
         let _engine = EngineBuilder::default()
@@ -337,4 +1179,417 @@ mod tests {
             .unwrap();
         // println!("{:#?}", engine.templates);
     }
+
+    #[test]
+    fn match_paths() {
+        assert!(matches_path("appendix.tex", Path::new("appendix.tex")).unwrap());
+        assert!(matches_path("code/", Path::new("code/listing.py")).unwrap());
+        assert!(matches_path("chapters/*.tex", Path::new("chapters/intro.tex")).unwrap());
+        assert!(!matches_path("code", Path::new("src/code.tex")).unwrap());
+    }
+
+    #[test]
+    fn binary_files() {
+        assert!(!is_binary("\\section{Résumé}\n".as_bytes()));
+        assert!(!is_binary(b""));
+        assert!(is_binary(b"OTTO\0\x01\0\0"));
+        assert!(is_binary(b"\xff\xd8\xff\xe0"));
+    }
+
+    #[test]
+    fn inheritance() {
+        let template = |dir: &str, config: &str| {
+            LoadedTemplateDir::from_config(toml::from_str(config).unwrap(), PathBuf::from(dir))
+        };
+        let available = [
+            template(
+                "templates/base",
+                r#"
+                name = "base-article"
+                copy = ["vendor/"]
+                [variables.title]
+                [variables.author]
+                default = "Anon"
+                "#,
+            ),
+            template(
+                "templates/report",
+                r#"
+                extends = "base-article"
+                copy = ["*.bib"]
+                [variables.author]
+                default = "Me"
+                [variables.dept]
+                "#,
+            ),
+            template("templates/a", r#"extends = "b""#),
+            template("templates/b", r#"extends = "a""#),
+            template("templates/orphan", r#"extends = "missing""#),
+        ];
+        let report = available[1].resolved(&available).unwrap();
+        assert_eq!(
+            report.config.variables.keys().collect::<Vec<_>>(),
+            ["title", "author", "dept"]
+        );
+        assert_eq!(
+            report.config.variables["author"].default,
+            Some(toml::Value::from("Me"))
+        );
+        assert_eq!(report.config.copy, ["vendor/", "*.bib"]);
+        assert!(available[2].ancestry(&available).is_err());
+        assert!(available[4].ancestry(&available).is_err());
+    }
+
+    #[test]
+    fn partials() {
+        assert!(is_partial(Path::new("_titlepage.tex")));
+        assert!(is_partial(Path::new("chapters/_common.tex")));
+        assert!(is_partial(Path::new("_templatex/macros.tex")));
+        assert!(!is_partial(Path::new("main_matter.tex")));
+        assert!(!is_output("@base-article/main.tex"));
+        assert!(is_output("main.tex"));
+    }
+
+    #[test]
+    fn copy_globs() {
+        let config: LoadedTemplateDirConfig =
+            toml::from_str(r#"copy = ["vendor/", "*.bib"]"#).unwrap();
+        assert!(config.is_copied(Path::new("vendor/tikz-uml.sty")).unwrap());
+        assert!(config.is_copied(Path::new("refs.bib")).unwrap());
+        assert!(!config.is_copied(Path::new("main.tex")).unwrap());
+    }
+
+    #[test]
+    fn path_variables() {
+        let config: LoadedTemplateDirConfig = toml::from_str(
+            r#"
+            [rename]
+            "figures/logo.png" = "<~{ institution | slugify }~>.png"
+            [files."chapter.tex"]
+            foreach = "chapters"
+            as = "chapter"
+            name = "<~{ prefix }~><~{ number }~>-<~{ chapter }~>.tex"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config
+                .path_variables(Path::new("chapters/<~{ chapter }~>/<~{ slug }~>.tex"))
+                .unwrap(),
+            ["chapter", "slug"]
+        );
+        assert_eq!(
+            config
+                .path_variables(Path::new("figures/logo.png"))
+                .unwrap(),
+            ["institution"]
+        );
+        assert!(
+            config
+                .path_variables(Path::new("main.tex"))
+                .unwrap()
+                .is_empty()
+        );
+    }
+    #[test]
+    fn foreach() {
+        let dir = template_dir(&[
+            (
+                "templatex.toml",
+                r#"
+                [files."chapters/chapter.tex"]
+                foreach = "chapters"
+                as = "chapter"
+                "#,
+            ),
+            ("chapters/chapter.tex", "\\chapter{<~{ chapter }~>}\n"),
+            (
+                "main.tex",
+                "{% for file in templatex.files['chapters/chapter.tex'] %}\\input{<~{ file }~>}\n{% endfor %}",
+            ),
+        ]);
+        let engine = engine(dir.path());
+        let mut template = engine.templates[0].clone();
+        let mut tera = template.tera.clone();
+        let path = Path::new("chapters/chapter.tex");
+        let mut context = tera::Context::new();
+        let chapters = (1..=100)
+            .map(|i| format!("Chapter {i}"))
+            .collect::<Vec<_>>();
+        context.insert("chapters", &chapters);
+        let outputs = template.outputs(path, &mut tera, &context).unwrap();
+        assert_eq!(outputs.len(), 100);
+        assert_eq!(outputs[0].0, Path::new("chapters/chapter-001.tex"));
+        assert_eq!(outputs[99].0, Path::new("chapters/chapter-100.tex"));
+        assert_eq!(
+            outputs[99].1.get("chapter"),
+            Some(&tera::Value::from("Chapter 100"))
+        );
+        for name in [
+            "../<~{ number }~>.tex",
+            "a//<~{ number }~>.tex",
+            "<~{ '' }~>",
+        ] {
+            template.config.files[0].name = Some(name.to_string());
+            assert!(
+                template.outputs(path, &mut tera, &context).is_err(),
+                "{name}"
+            );
+        }
+
+        let out_dir = dir.path().join("out");
+        let data = Answers::from([(
+            "chapters".to_string(),
+            tera::to_value(["Intro", "Methods"]).unwrap(),
+        )]);
+        engine.render(&out_dir, "article", &data).unwrap();
+        assert_eq!(
+            fs::read_to_string(out_dir.join("src/main.tex")).unwrap(),
+            "\\input{chapters/chapter-01.tex}\n\\input{chapters/chapter-02.tex}\n"
+        );
+        assert_eq!(
+            fs::read_to_string(out_dir.join("src/chapters/chapter-02.tex")).unwrap(),
+            "\\chapter{Methods}\n"
+        );
+    }
+    #[test]
+    fn build_artifacts() {
+        let dir = template_dir(&[
+            ("layout.tex", "\\documentclass{article}\n"),
+            ("main.aux", "\\relax\n"),
+            ("layout.pdf", "%PDF-1.5\n"),
+        ]);
+        let plot = dir.path().join("article/figures/plot.pdf");
+        fs::create_dir_all(plot.parent().unwrap()).unwrap();
+        fs::write(&plot, b"%PDF-1.5\n%\xe2\xe3\xcf\xd3\n").unwrap();
+        let engine = engine(dir.path());
+        assert_eq!(engine.templates[0].verbatim_files, [plot]);
+        let plan = engine
+            .plan(&dir.path().join("out"), "article", &Answers::new())
+            .unwrap();
+        let action = |source: &str| {
+            plan.entries
+                .iter()
+                .find(|e| e.source.as_deref() == Some(Path::new(source)))
+                .map(|e| e.action.clone())
+        };
+        assert_eq!(action("layout.tex"), Some(Action::Render));
+        assert_eq!(action("figures/plot.pdf"), Some(Action::Copy));
+        assert_eq!(
+            action("layout.pdf"),
+            Some(Action::Skip {
+                reason: SkipReason::BuildArtifact
+            })
+        );
+        assert_eq!(
+            action("main.aux"),
+            Some(Action::Skip {
+                reason: SkipReason::BuildArtifact
+            })
+        );
+    }
+    #[test]
+    fn staging() {
+        let dir = template_dir(&[
+            ("main.tex", "\\input{chapters/intro}\n"),
+            ("abstract.tex", "<~{ summary }~>\n"),
+            ("chapters/intro.tex", "<~{ intro }~>\n"),
+        ]);
+        let engine = engine(dir.path());
+        let out_dir = dir.path().join("out");
+        let leftovers = || {
+            fs::read_dir(dir.path())
+                .unwrap()
+                .map(|e| e.unwrap().file_name().display().to_string())
+                .filter(|name| name != "article")
+                .collect::<Vec<_>>()
+        };
+
+        let Err(Error::RenderErrors(RenderErrors(errors))) =
+            engine.render(&out_dir, "article", &Answers::new())
+        else {
+            panic!("rendering with missing variables succeeded");
+        };
+        let failed = errors.iter().map(|e| e.path.clone()).collect::<Vec<_>>();
+        assert_eq!(
+            failed,
+            ["src/abstract.tex", "src/chapters/intro.tex"].map(PathBuf::from)
+        );
+        // The message has the whole chain: Tera's error and the reason behind it.
+        assert!(
+            errors[0]
+                .message
+                .starts_with("Failed to render 'abstract.tex': ")
+                && errors[0].message.contains("Variable `summary` not found"),
+            "{}",
+            errors[0].message
+        );
+        assert!(leftovers().is_empty(), "{:?}", leftovers());
+
+        fs::create_dir_all(&out_dir).unwrap();
+        fs::write(out_dir.join("notes.txt"), "keep me").unwrap();
+        let data = Answers::from([
+            ("summary".to_string(), tera::Value::from("Short")),
+            ("intro".to_string(), tera::Value::from("Hello")),
+        ]);
+        engine.render(&out_dir, "article", &data).unwrap();
+        assert_eq!(leftovers(), ["out"]);
+        assert_eq!(
+            fs::read_to_string(out_dir.join("notes.txt")).unwrap(),
+            "keep me"
+        );
+        assert_eq!(
+            fs::read_to_string(out_dir.join("src/chapters/intro.tex")).unwrap(),
+            "Hello\n"
+        );
+        assert!(out_dir.join("src/main.tex").exists());
+        assert!(out_dir.join("Tectonic.toml").exists());
+    }
+    #[test]
+    fn parent_files() {
+        let dir = template_dir(&[
+            ("templatex.toml", r#"extends = "base-article""#),
+            (
+                "main.tex",
+                "{% extends \"@base-article/main.tex\" %}{% block body %}Report{% endblock body %}",
+            ),
+        ]);
+        let base = dir.path().join("base");
+        fs::create_dir_all(&base).unwrap();
+        fs::write(
+            base.join(CONFIG_FILE),
+            "name = \"base-article\"\nignore = true\n",
+        )
+        .unwrap();
+        fs::write(
+            base.join("main.tex"),
+            "\\begin{document}{% block body %}Base{% endblock body %}\\end{document}\n",
+        )
+        .unwrap();
+        fs::write(base.join("preamble.tex"), "\\usepackage{amsmath}\n").unwrap();
+        let article = dir.path().join("article");
+        let engine = EngineBuilder::default()
+            .template_dirs(vec![article.clone()])
+            .available_templates(vec![base.load_dir().unwrap(), article.load_dir().unwrap()])
+            .clone()
+            .build()
+            .unwrap();
+        let out_dir = dir.path().join("out");
+        engine.render(&out_dir, "article", &Answers::new()).unwrap();
+        assert_eq!(
+            fs::read_to_string(out_dir.join("src/main.tex")).unwrap(),
+            "\\begin{document}Report\\end{document}\n"
+        );
+        assert!(out_dir.join("src/preamble.tex").exists());
+    }
+    #[test]
+    fn preview() {
+        let dir = template_dir(&[("main.tex", "New\n"), ("intro.tex", "Intro\n")]);
+        let engine = engine(dir.path());
+        let out_dir = dir.path().join("out");
+        fs::create_dir_all(out_dir.join("src")).unwrap();
+        fs::write(out_dir.join("src/main.tex"), "Old\n").unwrap();
+        let err = engine
+            .preview(&out_dir, "article", &Answers::new(), ConflictPolicy::Refuse)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Refusing to overwrite"), "{err}");
+        assert!(err.contains("src/main.tex"), "{err}");
+
+        let plan = engine
+            .preview(&out_dir, "article", &Answers::new(), ConflictPolicy::Skip)
+            .unwrap();
+        let action = |source: &str| {
+            plan.entries
+                .iter()
+                .find(|e| e.source.as_deref() == Some(Path::new(source)))
+                .map(|e| e.action.clone())
+        };
+        assert_eq!(
+            action("main.tex"),
+            Some(Action::Skip {
+                reason: SkipReason::Exists
+            })
+        );
+        assert_eq!(action("intro.tex"), Some(Action::Render));
+        assert_eq!(
+            fs::read_to_string(out_dir.join("src/main.tex")).unwrap(),
+            "Old\n"
+        );
+    }
+    #[cfg(unix)]
+    #[test]
+    fn permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = template_dir(&[
+            ("templatex.toml", r#"copy = ["build.sh"]"#),
+            ("build.sh", "#!/bin/sh\ntectonic -X build\n"),
+        ]);
+        let script = dir.path().join("article/build.sh");
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        let out_dir = dir.path().join("out");
+        engine(dir.path())
+            .render(&out_dir, "article", &Answers::new())
+            .unwrap();
+        let mode = fs::metadata(out_dir.join("src/build.sh"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o755);
+    }
+    #[test]
+    fn shared_dir() {
+        let dir = template_dir(&[(
+            "main.tex",
+            "{% import \"@shared/macros.tex\" as macros %}{% include \"@shared/logo.tex\" %}\
+             <~{ macros::signature(name=\"Ada\") }~>\n",
+        )]);
+        let shared = dir.path().join("shared");
+        fs::create_dir_all(&shared).unwrap();
+        fs::write(shared.join("logo.tex"), "\\includegraphics{logo}\n").unwrap();
+        fs::write(
+            shared.join("macros.tex"),
+            "{% macro signature(name) %}-- <~{ name }~>{% endmacro signature %}",
+        )
+        .unwrap();
+        let article = dir.path().join("article");
+        assert_eq!(
+            template_dirs(vec![article.clone(), shared.clone()], Some(&shared)),
+            [article.as_path()]
+        );
+
+        let engine = EngineBuilder::default()
+            .template_dirs(vec![article])
+            .shared_dir(Some(shared))
+            .clone()
+            .build()
+            .unwrap();
+        let out_dir = dir.path().join("out");
+        let plan = engine.plan(&out_dir, "article", &Answers::new()).unwrap();
+        let paths = plan
+            .entries
+            .iter()
+            .flat_map(|e| [&e.source, &e.target])
+            .flatten()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                Path::new("main.tex"),
+                Path::new("src/main.tex"),
+                Path::new("Tectonic.toml")
+            ]
+        );
+        engine.render(&out_dir, "article", &Answers::new()).unwrap();
+        assert_eq!(
+            fs::read_to_string(out_dir.join("src/main.tex")).unwrap(),
+            "\\includegraphics{logo}\n-- Ada\n"
+        );
+        let written = glob(&out_dir.join("**/*").display().to_string())
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|f| f.is_file())
+            .count();
+        assert_eq!(written, 2);
+    }
 }
@@ -0,0 +1,600 @@
+use crate::{
+    errors::{Error, InputError, Result},
+    expr,
+};
+use color_eyre::eyre::eyre;
+use derive_more::Display;
+use indexmap::IndexMap;
+use regex::Regex;
+use serde::Deserialize;
+use tera::{Context, Tera, Value};
+
+/// Variables declared in the `[variables]` table of a `templatex.toml`, in declaration order.
+pub type Variables = IndexMap<String, VariableConfig>;
+
+/// The typed values that are inserted into the render context.
+pub type Answers = IndexMap<String, Value>;
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Display)]
+#[serde(rename_all = "lowercase")]
+pub enum VariableType {
+    #[default]
+    #[display("string")]
+    String,
+    #[display("multiline")]
+    Multiline,
+    #[display("bool")]
+    Bool,
+    #[display("int")]
+    Int,
+    #[display("float")]
+    Float,
+    #[display("choice")]
+    Choice,
+    #[display("list")]
+    List,
+}
+
+/// A single `[variables.<name>]` entry.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct VariableConfig {
+    #[serde(default, rename = "type")]
+    pub kind: VariableType,
+    /// The label shown when prompting. Defaults to `Enter value for <name>`.
+    pub prompt: Option<String>,
+    pub help: Option<String>,
+    /// The prefilled answer. String defaults may use `<~{ }~>` tags to build on earlier answers.
+    pub default: Option<toml::Value>,
+    /// A Tera expression over earlier answers. Computed variables are never prompted for.
+    pub compute: Option<String>,
+    /// A Tera expression over earlier answers. If it is false the variable is skipped.
+    pub when: Option<String>,
+    /// The value of a skipped variable. Without one, skipped variables are left undefined.
+    pub fallback: Option<toml::Value>,
+    #[serde(default)]
+    pub required: bool,
+    /// The options offered for `choice` variables, and for `list` variables if non-empty.
+    #[serde(default)]
+    pub choices: Vec<String>,
+    #[serde(default)]
+    pub validate: ValidationRules,
+}
+
+/// The `validate` table of a variable. Every rule that is set has to pass.
+///
+/// For `list` variables the length rules count items, while `pattern` and `allowed` apply to
+/// each item.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct ValidationRules {
+    /// A regular expression that the whole value has to match.
+    pub pattern: Option<String>,
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+    /// The smallest allowed value of an `int` or `float` variable.
+    pub min: Option<f64>,
+    /// The largest allowed value of an `int` or `float` variable.
+    pub max: Option<f64>,
+    pub allowed: Option<Vec<toml::Value>>,
+    /// A Tera expression that has to be true. The value being checked is available as `value`.
+    pub expr: Option<String>,
+    /// Shown instead of the default explanation when any of the rules fails.
+    pub message: Option<String>,
+}
+
+impl VariableConfig {
+    pub fn label(&self, name: &str) -> String {
+        self.prompt
+            .clone()
+            .unwrap_or_else(|| format!("Enter value for {}", name))
+    }
+
+    /// The declared default, converted to the variable's type.
+    pub fn default_value(&self, name: &str) -> Result<Option<Value>> {
+        self.convert(name, self.default.as_ref())
+    }
+
+    /// The declared fallback for when the variable is skipped, converted to its type.
+    pub fn fallback_value(&self, name: &str) -> Result<Option<Value>> {
+        self.convert(name, self.fallback.as_ref())
+    }
+
+    fn convert(&self, name: &str, value: Option<&toml::Value>) -> Result<Option<Value>> {
+        let Some(value) = value else {
+            return Ok(None);
+        };
+        let value = tera::to_value(value).map_err(|_| InputError::new(name))?;
+        Ok(Some(self.coerce(name, value)?))
+    }
+
+    /// Evaluates the `when` condition against the answers in `context`. Variables without
+    /// one are always asked for.
+    pub fn is_asked(&self, tera: &mut Tera, context: &Context) -> Result<bool> {
+        match &self.when {
+            Some(when) => expr::is_true(tera, when, context),
+            None => Ok(true),
+        }
+    }
+
+    /// The default with any `<~{ }~>` tags rendered against the answers in `context`.
+    pub fn resolve_default(
+        &self,
+        name: &str,
+        tera: &mut Tera,
+        context: &Context,
+    ) -> Result<Option<Value>> {
+        match &self.default {
+            Some(toml::Value::String(s)) if expr::is_template(s) => {
+                let rendered = expr::render(tera, s, context)?;
+                Ok(Some(self.parse(name, &rendered)?))
+            }
+            _ => self.default_value(name),
+        }
+    }
+
+    /// Evaluates the `compute` expression, if any, against the answers in `context`.
+    pub fn compute_value(
+        &self,
+        name: &str,
+        tera: &mut Tera,
+        context: &Context,
+    ) -> Result<Option<Value>> {
+        let Some(compute) = &self.compute else {
+            return Ok(None);
+        };
+        let value = expr::evaluate(tera, compute, context)?;
+        Ok(Some(self.coerce(name, value)?))
+    }
+
+    /// The variables that `compute`, `when`, a templated `default` and the `validate`
+    /// expression read.
+    pub fn dependencies(&self) -> Result<Vec<String>> {
+        let mut deps = Vec::new();
+        for expr in [&self.compute, &self.when].into_iter().flatten() {
+            deps.extend(expr::dependencies(expr)?);
+        }
+        if let Some(expr) = &self.validate.expr {
+            deps.extend(
+                expr::dependencies(expr)?
+                    .into_iter()
+                    .filter(|d| d != "value"),
+            );
+        }
+        if let Some(toml::Value::String(s)) = &self.default
+            && expr::is_template(s)
+        {
+            deps.extend(expr::template_dependencies(s)?);
+        }
+        Ok(deps)
+    }
+
+    /// Parses a raw string, e.g. from the command line, into the variable's type.
+    pub fn parse(&self, name: &str, raw: &str) -> std::result::Result<Value, InputError> {
+        self.coerce(name, Value::String(raw.to_string()))
+    }
+
+    /// Converts `value` into the variable's type, parsing strings where necessary.
+    pub fn coerce(&self, name: &str, value: Value) -> std::result::Result<Value, InputError> {
+        let invalid = |reason: &str| InputError::with_reason(name, reason);
+        let value = match (self.kind, value) {
+            (VariableType::String | VariableType::Multiline, Value::String(s)) => Value::String(s),
+            (
+                VariableType::String | VariableType::Multiline,
+                v @ (Value::Bool(_) | Value::Number(_)),
+            ) => Value::String(v.to_string()),
+            (VariableType::Choice, v) => {
+                let s = match v {
+                    Value::String(s) => s,
+                    v @ (Value::Bool(_) | Value::Number(_)) => v.to_string(),
+                    _ => return Err(invalid("Expected a single value.")),
+                };
+                if !self.choices.contains(&s) {
+                    return Err(invalid(&format!(
+                        "Expected one of: {}.",
+                        self.choices.join(", ")
+                    )));
+                }
+                Value::String(s)
+            }
+            (VariableType::Bool, Value::Bool(b)) => Value::Bool(b),
+            (VariableType::Bool, Value::String(s)) => match s.trim().to_lowercase().as_str() {
+                "true" | "yes" | "y" | "1" | "on" => Value::Bool(true),
+                "false" | "no" | "n" | "0" | "off" | "" => Value::Bool(false),
+                _ => return Err(invalid("Expected yes or no.")),
+            },
+            (VariableType::Int, Value::Number(n)) => n
+                .as_i64()
+                .ok_or_else(|| invalid("Expected a whole number."))?
+                .into(),
+            (VariableType::Int, Value::String(s)) => s
+                .trim()
+                .parse::<i64>()
+                .map_err(|_| invalid("Expected a whole number."))?
+                .into(),
+            (VariableType::Float, Value::Number(n)) => n
+                .as_f64()
+                .ok_or_else(|| invalid("Expected a number."))?
+                .into(),
+            (VariableType::Float, Value::String(s)) => s
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| invalid("Expected a number."))?
+                .into(),
+            (VariableType::List, Value::Array(items)) => Value::Array(items),
+            (VariableType::List, Value::String(s)) => Value::Array(
+                s.split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(|item| Value::String(item.to_string()))
+                    .collect(),
+            ),
+            (kind, _) => return Err(invalid(&format!("Expected a value of type {kind}."))),
+        };
+        // Like the options of a `choice`, those of a `list` are the only values it can have.
+        if let Value::Array(items) = &value
+            && !self.choices.is_empty()
+            && let Some(item) = items
+                .iter()
+                .find(|item| !self.choices.contains(&display_value(item)))
+        {
+            return Err(invalid(&format!(
+                "`{}` is not one of: {}.",
+                display_value(item),
+                self.choices.join(", ")
+            )));
+        }
+        Ok(value)
+    }
+
+    /// Checks that `value` isn't empty if the variable is required, and checks it against the
+    /// `validate` rules. Expressions can read the answers in `context` as well as the value
+    /// itself, as `value`.
+    pub fn validate(
+        &self,
+        name: &str,
+        value: &Value,
+        tera: &mut Tera,
+        context: &Context,
+    ) -> std::result::Result<(), InputError> {
+        let is_empty = match value {
+            Value::Null => true,
+            Value::String(s) => s.trim().is_empty(),
+            Value::Array(items) => items.is_empty(),
+            _ => false,
+        };
+        if self.required && is_empty {
+            return Err(InputError::with_reason(name, "A value is required."));
+        }
+        let rules = &self.validate;
+        rules.check(value, tera, context).map_err(|reason| {
+            InputError::with_reason(name, rules.message.clone().unwrap_or(reason))
+        })
+    }
+}
+
+impl ValidationRules {
+    /// Returns why `value` breaks one of the rules, if it does.
+    fn check(
+        &self,
+        value: &Value,
+        tera: &mut Tera,
+        context: &Context,
+    ) -> std::result::Result<(), String> {
+        let items = match value {
+            Value::Array(items) => items.iter().collect::<Vec<_>>(),
+            value => vec![value],
+        };
+        let length = match value {
+            Value::Array(items) => Some(items.len()),
+            Value::String(s) => Some(s.chars().count()),
+            _ => None,
+        };
+        if let (Some(min), Some(length)) = (self.min_length, length)
+            && length < min
+        {
+            return Err(format!("Must be at least {min} long."));
+        }
+        if let (Some(max), Some(length)) = (self.max_length, length)
+            && length > max
+        {
+            return Err(format!("Must be at most {max} long."));
+        }
+        if let Some(number) = value.as_f64() {
+            if let Some(min) = self.min
+                && number < min
+            {
+                return Err(format!("Must be at least {min}."));
+            }
+            if let Some(max) = self.max
+                && number > max
+            {
+                return Err(format!("Must be at most {max}."));
+            }
+        }
+        if let Some(pattern) = &self.pattern {
+            let regex = Regex::new(&format!("^(?:{pattern})$"))
+                .map_err(|e| format!("Invalid pattern `{pattern}`: {e}"))?;
+            if let Some(item) = items
+                .iter()
+                .find(|item| !regex.is_match(&display_value(item)))
+            {
+                return Err(format!(
+                    "`{}` does not match the pattern `{pattern}`.",
+                    display_value(item)
+                ));
+            }
+        }
+        if let Some(allowed) = &self.allowed {
+            let allowed = allowed
+                .iter()
+                .filter_map(|a| tera::to_value(a).ok())
+                .map(|a| display_value(&a))
+                .collect::<Vec<_>>();
+            if let Some(item) = items
+                .iter()
+                .find(|item| !allowed.contains(&display_value(item)))
+            {
+                return Err(format!(
+                    "`{}` is not one of: {}.",
+                    display_value(item),
+                    allowed.join(", ")
+                ));
+            }
+        }
+        if let Some(expr) = &self.expr {
+            let mut context = context.clone();
+            context.insert("value", value);
+            if !expr::is_true(tera, expr, &context).map_err(|e| e.to_string())? {
+                return Err(format!("Must satisfy `{expr}`."));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Orders `names` so that every variable comes after the variables its `compute`, `when`,
+/// `default` or `validate` expression reads, keeping the original order wherever possible.
+/// Fails if the dependencies form a cycle.
+pub fn resolution_order(names: &[String], declared: &Variables) -> Result<Vec<String>> {
+    let mut pending = Vec::new();
+    for name in names {
+        let deps = match declared.get(name) {
+            Some(config) => config.dependencies()?,
+            None => Vec::new(),
+        };
+        let deps = deps
+            .into_iter()
+            .filter(|dep| dep != name && names.contains(dep))
+            .collect::<Vec<_>>();
+        pending.push((name.clone(), deps));
+    }
+    let mut order: Vec<String> = Vec::new();
+    while !pending.is_empty() {
+        let Some(next) = pending
+            .iter()
+            .position(|(_, deps)| deps.iter().all(|dep| order.contains(dep)))
+        else {
+            return Err(dependency_cycle(&pending));
+        };
+        order.push(pending.remove(next).0);
+    }
+    Ok(order)
+}
+
+/// Builds the error for a set of variables that can't be ordered, naming one of the cycles.
+fn dependency_cycle(pending: &[(String, Vec<String>)]) -> Error {
+    let deps_of = |name: &str| {
+        pending
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, deps)| deps.as_slice())
+            .unwrap_or_default()
+    };
+    // Every pending variable waits on another pending one, so walking the first
+    // unresolved dependency from anywhere must eventually revisit a variable.
+    let mut path = vec![pending[0].0.clone()];
+    loop {
+        let current = path.last().unwrap();
+        let Some(next) = deps_of(current)
+            .iter()
+            .find(|dep| pending.iter().any(|(n, _)| n == *dep))
+        else {
+            break;
+        };
+        if let Some(start) = path.iter().position(|p| p == next) {
+            path.drain(..start);
+            path.push(next.clone());
+            break;
+        }
+        path.push(next.clone());
+    }
+    Error::Other(eyre!(
+        "Variables depend on each other in a cycle: {}",
+        path.join(" -> ")
+    ))
+}
+
+/// Renders a value the way it should be shown as a prefilled text answer.
+pub fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items
+            .iter()
+            .map(display_value)
+            .collect::<Vec<_>>()
+            .join(", "),
+        v => v.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::templating::LoadedTemplateDirConfig;
+    #[test]
+    fn test() {
+        let conf: LoadedTemplateDirConfig = toml::from_str(
+            r#"
+            name = "article"
+            [variables.title]
+            prompt = "Title"
+            required = true
+            [variables.twocolumn]
+            type = "bool"
+            default = false
+            [variables.packages]
+            type = "list"
+            default = ["amsmath", "graphicx"]
+            [variables.fontsize]
+            type = "choice"
+            choices = ["10pt", "11pt", "12pt"]
+            default = "11pt"
+            "#,
+        )
+        .unwrap();
+        let names = conf.variables.keys().collect::<Vec<_>>();
+        assert_eq!(names, ["title", "twocolumn", "packages", "fontsize"]);
+        let twocolumn = &conf.variables["twocolumn"];
+        assert_eq!(
+            twocolumn.parse("twocolumn", "yes").unwrap(),
+            Value::Bool(true)
+        );
+        let packages = &conf.variables["packages"];
+        assert_eq!(
+            packages.default_value("packages").unwrap().unwrap(),
+            tera::to_value(["amsmath", "graphicx"]).unwrap()
+        );
+        assert_eq!(
+            packages.parse("packages", "a, b,").unwrap(),
+            tera::to_value(["a", "b"]).unwrap()
+        );
+        assert!(conf.variables["fontsize"].parse("fontsize", "9pt").is_err());
+        let languages = VariableConfig {
+            kind: VariableType::List,
+            choices: vec!["english".into(), "ngerman".into()],
+            ..Default::default()
+        };
+        assert_eq!(
+            languages.parse("languages", "ngerman, english").unwrap(),
+            tera::to_value(["ngerman", "english"]).unwrap()
+        );
+        assert!(languages.parse("languages", "english, klingon").is_err());
+        assert!(
+            languages
+                .coerce("languages", tera::to_value(["klingon"]).unwrap())
+                .is_err()
+        );
+        assert!(
+            VariableConfig {
+                kind: VariableType::Int,
+                ..Default::default()
+            }
+            .parse("year", "20x6")
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn derived() {
+        let derived: Variables = toml::from_str(
+            r#"
+            slug = { compute = "title | slugify" }
+            short = { default = "<~{ title | truncate(length=10) }~>" }
+            title = {}
+            year = { type = "int", compute = "date | truncate(length=4, end='')" }
+            "#,
+        )
+        .unwrap();
+        let names = ["slug", "short", "title", "year"].map(String::from);
+        assert_eq!(
+            resolution_order(&names, &derived).unwrap(),
+            ["title", "slug", "short", "year"]
+        );
+        let mut tera = Tera::default();
+        let mut context = Context::new();
+        context.insert("date", "2026-10-18");
+        assert_eq!(
+            derived["year"]
+                .compute_value("year", &mut tera, &context)
+                .unwrap(),
+            Some(Value::from(2026))
+        );
+        let cyclic: Variables = toml::from_str(
+            r#"
+            a = { compute = "b" }
+            b = { default = "<~{ c }~>" }
+            c = { compute = "a ~ b" }
+            "#,
+        )
+        .unwrap();
+        let names = ["a", "b", "c"].map(String::from);
+        let err = resolution_order(&names, &cyclic).unwrap_err().to_string();
+        assert!(err.contains("a -> b -> c -> a"), "{err}");
+    }
+
+    #[test]
+    fn conditions() {
+        let conditional: Variables = toml::from_str(
+            r#"
+            advisor = { when = "doc_type == 'thesis'", fallback = "none" }
+            doc_type = { type = "choice", choices = ["article", "thesis"] }
+            handout = { type = "bool", when = "slides" }
+            "#,
+        )
+        .unwrap();
+        let names = ["advisor", "doc_type"].map(String::from);
+        assert_eq!(
+            resolution_order(&names, &conditional).unwrap(),
+            ["doc_type", "advisor"]
+        );
+        let mut tera = Tera::default();
+        let mut context = Context::new();
+        context.insert("doc_type", "article");
+        let advisor = &conditional["advisor"];
+        assert!(!advisor.is_asked(&mut tera, &context).unwrap());
+        assert_eq!(
+            advisor.fallback_value("advisor").unwrap(),
+            Some(Value::from("none"))
+        );
+        // `slides` was skipped and has no fallback, so it isn't in the context.
+        assert!(
+            !conditional["handout"]
+                .is_asked(&mut tera, &context)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn validation() {
+        let rules: Variables = toml::from_str(
+            r#"
+            orcid = { validate = { pattern = '\d{4}-\d{4}-\d{4}-\d{3}[\dX]' } }
+            title = { validate = { min_length = 3, max_length = 10 } }
+            chapters = { type = "int", validate = { min = 1, max = 20 } }
+            lang = { type = "list", validate = { allowed = ["en", "de"], max_length = 2 } }
+            end = { type = "int", validate = { expr = "value >= start", message = "Ends too early" } }
+            "#,
+        )
+        .unwrap();
+        let mut tera = Tera::default();
+        let mut context = Context::new();
+        context.insert("start", &2020);
+        let mut check = |name: &str, raw: &str| {
+            let value = rules[name].parse(name, raw).unwrap();
+            rules[name]
+                .validate(name, &value, &mut tera, &context)
+                .map_err(|e| e.reason().to_string())
+        };
+        assert!(check("orcid", "0000-0002-1825-0097").is_ok());
+        assert!(check("orcid", "x0000-0002-1825-0097").is_err());
+        assert!(check("title", "ab").is_err());
+        assert!(check("title", "Ünïcödé").is_ok());
+        assert!(check("chapters", "0").is_err());
+        assert!(check("chapters", "20").is_ok());
+        assert!(check("lang", "en, de").is_ok());
+        assert!(check("lang", "en, fr").unwrap_err().contains("fr"));
+        assert!(check("lang", "en, de, en").is_err());
+        assert!(check("end", "2026").is_ok());
+        assert_eq!(check("end", "2019").unwrap_err(), "Ends too early");
+        assert_eq!(rules["end"].dependencies().unwrap(), ["start"]);
+    }
+}